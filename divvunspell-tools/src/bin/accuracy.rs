@@ -5,7 +5,7 @@ use std::{
 };
 
 use divvunspell::archive::{SpellerArchive, ZipSpellerArchive};
-use divvunspell::speller::suggestion::Suggestion;
+use divvunspell::speller::suggestion::{SearchStats, Suggestion};
 use divvunspell::speller::{CaseHandlingConfig, SpellerConfig};
 use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
@@ -18,6 +18,17 @@ static CFG: SpellerConfig = SpellerConfig {
     beam: None,
     case_handling: Some(CaseHandlingConfig::default()),
     node_pool_size: 128,
+    no_suggest_tags: Vec::new(),
+    no_accept_tags: Vec::new(),
+    max_iterations: Some(10_000_000),
+    time_limit: None,
+    cancellation: None,
+    alignment: false,
+    fuzzy_completion: false,
+    context_weight: 1.0,
+    split_penalty: None,
+    join_penalty: 5.0,
+    rankers: Vec::new(),
 };
 
 fn load_words(
@@ -62,6 +73,7 @@ struct AccuracyResult<'a> {
     suggestions: Vec<Suggestion>,
     position: Option<usize>,
     time: Time,
+    stats: SearchStats,
 }
 
 #[derive(Debug, Serialize)]
@@ -238,7 +250,8 @@ fn main() -> Result<(), Box<dyn Error>> {
         .progress_with(pb)
         .map(|(input, expected)| {
             let now = Instant::now();
-            let suggestions = archive.speller().suggest_with_config(&input, &cfg);
+            let result = archive.speller().suggest_detailed(&input, &cfg);
+            let suggestions = result.suggestions;
             let now = now.elapsed();
 
             let time = Time {
@@ -254,6 +267,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 time,
                 suggestions,
                 position,
+                stats: result.stats,
             }
         })
         .collect::<Vec<_>>();
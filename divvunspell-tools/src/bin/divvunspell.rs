@@ -1,7 +1,8 @@
-use std::io::{self, Read};
+use std::io::{self, BufRead, Read};
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
+    time::Duration,
 };
 
 use gumdrop::Options;
@@ -80,21 +81,48 @@ impl OutputWriter for JsonWriter {
     }
 }
 
+/// How many words are checked at a time. Each chunk's results are written
+/// before the next is read, so long inputs neither wait for the end nor are
+/// held in memory whole.
+const CHUNK_SIZE: usize = 1024;
+
 fn run(
-    speller: Arc<dyn Speller + Send>,
-    words: Vec<String>,
+    speller: Arc<dyn Speller + Send + Sync>,
+    mut words: impl Iterator<Item = String>,
     writer: &mut dyn OutputWriter,
     is_suggesting: bool,
     is_always_suggesting: bool,
     suggest_cfg: &SpellerConfig,
 ) {
-    for word in words {
-        let is_correct = speller.clone().is_correct(&word);
-        writer.write_correction(&word, is_correct);
+    loop {
+        let chunk = words.by_ref().take(CHUNK_SIZE).collect::<Vec<_>>();
+
+        if chunk.is_empty() {
+            break;
+        }
 
-        if is_suggesting && (is_always_suggesting || !is_correct) {
-            let suggestions = speller.clone().suggest_with_config(&word, &suggest_cfg);
-            writer.write_suggestions(&word, &suggestions);
+        let chunk = chunk.iter().map(String::as_str).collect::<Vec<_>>();
+        let results = speller
+            .clone()
+            .check_many(&chunk, &SpellerConfig::default());
+
+        let to_suggest = chunk
+            .iter()
+            .zip(results.iter())
+            .filter(|(_, is_correct)| is_suggesting && (is_always_suggesting || !**is_correct))
+            .map(|(word, _)| *word)
+            .collect::<Vec<_>>();
+        let mut suggestions = speller
+            .clone()
+            .suggest_many(&to_suggest, &suggest_cfg)
+            .into_iter();
+
+        for (word, is_correct) in chunk.into_iter().zip(results) {
+            writer.write_correction(word, is_correct);
+
+            if is_suggesting && (is_always_suggesting || !is_correct) {
+                writer.write_suggestions(word, &suggestions.next().unwrap());
+            }
         }
     }
 }
@@ -134,6 +162,9 @@ struct SuggestArgs {
     #[options(help = "maximum number of results")]
     nbest: Option<usize>,
 
+    #[options(help = "time limit in seconds for each suggestion lookup")]
+    time_limit: Option<f32>,
+
     #[options(
         no_short,
         long = "no-case-handling",
@@ -245,21 +276,30 @@ fn suggest(args: SuggestArgs) -> anyhow::Result<()> {
         }
     }
 
+    if let Some(v) = args.time_limit.filter(|x| x > &0.0) {
+        suggest_cfg.time_limit = Some(Duration::from_secs_f32(v));
+    }
+
     let mut writer: Box<dyn OutputWriter> = if args.use_json {
         Box::new(JsonWriter::new())
     } else {
         Box::new(StdoutWriter)
     };
 
-    let words = if args.inputs.is_empty() {
+    let words: Box<dyn Iterator<Item = String>> = if args.inputs.is_empty() {
         eprintln!("Reading from stdin...");
-        let mut buffer = String::new();
-        io::stdin()
-            .read_to_string(&mut buffer)
-            .expect("reading stdin");
-        buffer.split(" ").map(|x| x.trim().to_string()).collect()
+        Box::new(
+            io::BufReader::new(io::stdin())
+                .lines()
+                .map(|line| line.expect("reading stdin"))
+                .flat_map(|line| {
+                    line.split(" ")
+                        .map(|x| x.trim().to_string())
+                        .collect::<Vec<_>>()
+                }),
+        )
     } else {
-        args.inputs.into_iter().map(|x| x.to_string()).collect()
+        Box::new(args.inputs.into_iter())
     };
 
     let archive = load_archive(&args.archive).unwrap();
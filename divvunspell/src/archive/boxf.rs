@@ -1,9 +1,12 @@
+use std::io::BufReader;
 use std::sync::Arc;
 
 use box_format::BoxFileReader;
 
 use super::error::SpellerArchiveError;
-use super::{meta::SpellerMetadata, SpellerArchive};
+use super::{meta::SpellerMetadata, SpellerArchive, BLOCKLIST, LANGUAGE_MODEL};
+use crate::speller::blocklist::Blocklist;
+use crate::speller::ngram::NgramModel;
 use crate::speller::{HfstSpeller, Speller};
 use crate::transducer::{
     thfst::{MemmapThfstChunkedTransducer, MemmapThfstTransducer},
@@ -63,7 +66,7 @@ where
 
         let fs = BoxFilesystem::new(&archive);
 
-        let metadata = fs
+        let metadata: Option<SpellerMetadata> = fs
             .open("meta.json")
             .ok()
             .and_then(|x| serde_json::from_reader(x).ok());
@@ -73,6 +76,27 @@ where
             U::from_path(&fs, "acceptor.default.thfst").map_err(SpellerArchiveError::Transducer)?;
 
         let speller = HfstSpeller::new(errmodel, acceptor);
+
+        if let Ok(file) = fs.open(LANGUAGE_MODEL) {
+            let model = NgramModel::from_arpa(BufReader::new(file))
+                .map_err(|e| SpellerArchiveError::Io(LANGUAGE_MODEL.into(), e))?;
+            speller.set_language_model(Some(Arc::new(model)));
+        }
+
+        if let Ok(file) = fs.open(BLOCKLIST) {
+            speller
+                .blocklist()
+                .read(BufReader::new(file))
+                .map_err(|e| SpellerArchiveError::Io(BLOCKLIST.into(), e))?;
+        }
+
+        if let Some(form) = metadata
+            .as_ref()
+            .and_then(|x| x.acceptor.normalization_form())
+        {
+            speller.set_normalization(Some(form));
+        }
+
         Ok(BoxSpellerArchive { speller, metadata })
     }
 
@@ -83,6 +107,10 @@ where
     fn metadata(&self) -> Option<&SpellerMetadata> {
         self.metadata.as_ref()
     }
+
+    fn blocklist(&self) -> &Blocklist {
+        self.speller.blocklist()
+    }
 }
 
 #[cfg(feature = "internal_ffi")]
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use serde_xml_rs::{from_reader, Error, ParserConfig};
 
+use crate::types::NormalizationForm;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SpellerMetadata {
     pub info: SpellerMetadataInfo,
@@ -30,6 +32,16 @@ pub struct SpellerMetadataAcceptor {
     pub id: String,
     pub title: Vec<SpellerTitle>,
     pub description: String,
+    /// The Unicode normalization form of the lexicon's symbols, `nfc` or
+    /// `nfd`, for when it cannot be told from the alphabet.
+    #[serde(default)]
+    pub normalization: Option<String>,
+}
+
+impl SpellerMetadataAcceptor {
+    pub fn normalization_form(&self) -> Option<NormalizationForm> {
+        self.normalization.as_ref().and_then(|x| x.parse().ok())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -10,8 +10,17 @@ pub use self::boxf::BoxSpellerArchive;
 use self::error::SpellerArchiveError;
 use self::meta::SpellerMetadata;
 pub use self::zip::ZipSpellerArchive;
+use crate::speller::blocklist::Blocklist;
 use crate::{speller::Speller, transducer, vfs};
 
+/// An optional n-gram language model in ARPA format, used to rank
+/// suggestions in context.
+pub(crate) const LANGUAGE_MODEL: &str = "lm.default.arpa";
+
+/// An optional list of words never to be suggested, in the format read by
+/// `Blocklist::read`.
+pub(crate) const BLOCKLIST: &str = "blocklist.txt";
+
 pub(crate) struct TempMmap {
     mmap: Arc<Mmap>,
 
@@ -40,6 +49,9 @@ pub trait SpellerArchive {
 
     fn speller(&self) -> Arc<dyn Speller + Send + Sync>;
     fn metadata(&self) -> Option<&SpellerMetadata>;
+    /// The speller's blocklist, holding the archive's own entries, if any,
+    /// along with those added since.
+    fn blocklist(&self) -> &Blocklist;
 }
 
 pub fn open<P, T, U>(path: P) -> Result<Arc<dyn SpellerArchive>, SpellerArchiveError>
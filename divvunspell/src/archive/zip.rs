@@ -1,3 +1,4 @@
+use ::zip::result::ZipError;
 use ::zip::{CompressionMethod, ZipArchive};
 use memmap::MmapOptions;
 use std::fs::File;
@@ -7,7 +8,9 @@ use std::sync::Arc;
 
 use super::error::SpellerArchiveError;
 use super::meta::SpellerMetadata;
-use super::{MmapRef, SpellerArchive, TempMmap};
+use super::{MmapRef, SpellerArchive, TempMmap, BLOCKLIST, LANGUAGE_MODEL};
+use crate::speller::blocklist::Blocklist;
+use crate::speller::ngram::NgramModel;
 use crate::speller::{HfstSpeller, Speller};
 use crate::transducer::hfst::HfstTransducer;
 
@@ -90,12 +93,34 @@ impl SpellerArchive for ZipSpellerArchive {
             .map_err(|e| SpellerArchiveError::Io(acceptor_id.into(), e))?;
         let errmodel_mmap = mmap_by_name(&mut file, &mut archive, &errmodel_id)
             .map_err(|e| SpellerArchiveError::Io(errmodel_id.into(), e))?;
+        let language_model = match archive.by_name(LANGUAGE_MODEL) {
+            Ok(index) => Some(
+                NgramModel::from_arpa(std::io::BufReader::new(index))
+                    .map_err(|e| SpellerArchiveError::Io(LANGUAGE_MODEL.into(), e))?,
+            ),
+            Err(ZipError::FileNotFound) => None,
+            Err(e) => return Err(SpellerArchiveError::Io(LANGUAGE_MODEL.into(), e.into())),
+        };
+        let blocklist = Blocklist::new();
+        match archive.by_name(BLOCKLIST) {
+            Ok(index) => blocklist
+                .read(std::io::BufReader::new(index))
+                .map_err(|e| SpellerArchiveError::Io(BLOCKLIST.into(), e))?,
+            Err(ZipError::FileNotFound) => {}
+            Err(e) => return Err(SpellerArchiveError::Io(BLOCKLIST.into(), e.into())),
+        };
         drop(archive);
 
         let acceptor = HfstTransducer::from_mapped_memory(acceptor_mmap.map());
         let errmodel = HfstTransducer::from_mapped_memory(errmodel_mmap.map());
 
         let speller = HfstSpeller::new(errmodel, acceptor);
+        speller.set_language_model(language_model.map(Arc::new));
+        speller.blocklist().extend(&blocklist);
+
+        if let Some(form) = metadata.acceptor.normalization_form() {
+            speller.set_normalization(Some(form));
+        }
 
         Ok(ZipSpellerArchive { metadata, speller })
     }
@@ -107,6 +132,10 @@ impl SpellerArchive for ZipSpellerArchive {
     fn metadata(&self) -> Option<&SpellerMetadata> {
         Some(&self.metadata)
     }
+
+    fn blocklist(&self) -> &Blocklist {
+        self.speller.blocklist()
+    }
 }
 
 #[cfg(feature = "internal_ffi")]
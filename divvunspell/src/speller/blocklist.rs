@@ -0,0 +1,183 @@
+use std::io::{self, BufRead};
+
+use hashbrown::HashSet;
+use parking_lot::RwLock;
+use smol_str::SmolStr;
+
+use super::suggestion::Suggestion;
+use crate::tokenizer::case_handling::lower_case;
+use crate::types::NormalizationForm;
+
+/// Words the speller must never suggest, even though the lexicon accepts
+/// them, such as offensive or archaic forms. They are still accepted when
+/// typed.
+///
+/// Entries match regardless of case. An entry with a `*` is a pattern, where
+/// each `*` stands for any run of characters, so `thou*` blocks both "thou"
+/// and "thoughtful". A suggestion of several words is blocked if any of its
+/// words is.
+///
+/// Lists are read as plain text, one entry per line. Blank lines and lines
+/// starting with `#` are skipped.
+#[derive(Debug, Default)]
+pub struct Blocklist {
+    entries: RwLock<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    words: HashSet<SmolStr>,
+    patterns: Vec<SmolStr>,
+}
+
+impl Blocklist {
+    pub fn new() -> Blocklist {
+        Blocklist::default()
+    }
+
+    pub fn add(&self, entry: &str) {
+        let entry = folded(entry);
+        let mut entries = self.entries.write();
+
+        if entry.contains('*') {
+            if !entries.patterns.contains(&entry) {
+                entries.patterns.push(entry);
+            }
+        } else {
+            entries.words.insert(entry);
+        }
+    }
+
+    pub fn remove(&self, entry: &str) -> bool {
+        let entry = folded(entry);
+        let mut entries = self.entries.write();
+        let count = entries.patterns.len();
+
+        entries.patterns.retain(|x| *x != entry);
+        entries.words.remove(&entry) || entries.patterns.len() != count
+    }
+
+    /// Adds every entry of another blocklist.
+    pub fn extend(&self, other: &Blocklist) {
+        let other = other.entries.read();
+        let mut entries = self.entries.write();
+
+        entries.words.extend(other.words.iter().cloned());
+        for pattern in other.patterns.iter() {
+            if !entries.patterns.contains(pattern) {
+                entries.patterns.push(pattern.clone());
+            }
+        }
+    }
+
+    /// Adds every entry of a list.
+    pub fn read<R: BufRead>(&self, reader: R) -> io::Result<()> {
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            self.add(line);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        let entries = self.entries.read();
+        entries.words.is_empty() && entries.patterns.is_empty()
+    }
+
+    pub fn is_blocked(&self, word: &str) -> bool {
+        let entries = self.entries.read();
+        word.split(' ').any(|word| entries.matches(word))
+    }
+
+    /// Drops the blocked suggestions.
+    pub(crate) fn retain(&self, suggestions: &mut Vec<Suggestion>) {
+        if !self.is_empty() {
+            suggestions.retain(|x| !self.is_blocked(x.value()));
+        }
+    }
+}
+
+impl Entries {
+    fn matches(&self, word: &str) -> bool {
+        let word = folded(word);
+
+        self.words.contains(&word)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &word))
+    }
+}
+
+/// The form entries and words are compared in: lower case NFC, so neither
+/// case nor the lexicon's normalization form matter.
+fn folded(word: &str) -> SmolStr {
+    lower_case(&NormalizationForm::Nfc.normalize(word))
+}
+
+/// Whether `text` matches `pattern`, where each `*` stands for any run of
+/// characters.
+fn matches_pattern(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let rest = parts.collect::<Vec<_>>();
+
+    let mut text = match text.strip_prefix(first) {
+        Some(text) => text,
+        None => return false,
+    };
+
+    let (last, middle) = match rest.split_last() {
+        Some(split) => split,
+        None => return text.is_empty(),
+    };
+
+    for part in middle {
+        match text.find(part) {
+            Some(index) => text = &text[index + part.len()..],
+            None => return false,
+        }
+    }
+
+    text.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("ab*", "abc"));
+        assert!(matches_pattern("*bc", "abc"));
+        assert!(matches_pattern("a*c", "abc"));
+        assert!(matches_pattern("*b*", "abc"));
+        assert!(matches_pattern("a*b*c", "abc"));
+        assert!(!matches_pattern("a*b*c", "acb"));
+        assert!(!matches_pattern("ab*ba", "aba"));
+        assert!(!matches_pattern("abc", "abcd"));
+    }
+
+    #[test]
+    fn blocks_words_and_patterns() {
+        let blocklist = Blocklist::new();
+        blocklist
+            .read("# archaic\nthee\n\nthou*\n".as_bytes())
+            .unwrap();
+
+        assert!(blocklist.is_blocked("Thee"));
+        assert!(blocklist.is_blocked("thousest"));
+        assert!(blocklist.is_blocked("for thee"));
+        assert!(!blocklist.is_blocked("the"));
+
+        assert!(blocklist.remove("THOU*"));
+        assert!(!blocklist.is_blocked("thou"));
+    }
+}
@@ -0,0 +1,245 @@
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use super::ranker::SuggestionRanker;
+use super::suggestion::{CheckResult, SuggestResult, Suggestion, Truncation};
+use super::{Speller, SpellerConfig};
+use crate::tokenizer::WordContext;
+
+/// Hit and miss counts of a `CachedSpeller`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub check_hits: usize,
+    pub check_misses: usize,
+    pub suggest_hits: usize,
+    pub suggest_misses: usize,
+}
+
+/// The parts of a `SpellerConfig` that can change a lookup's result. Time
+/// limits and cancellation are left out, as results they cut short are never
+/// cached.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ConfigKey {
+    n_best: Option<usize>,
+    max_weight: Option<u32>,
+    beam: Option<u32>,
+    case_handling: Option<[u32; 3]>,
+    no_suggest_tags: Vec<SmolStr>,
+    no_accept_tags: Vec<SmolStr>,
+    max_iterations: Option<usize>,
+    alignment: bool,
+    split_penalty: Option<u32>,
+    rankers: Vec<RankerKey>,
+}
+
+/// A config's ranker, compared by identity. Holding on to it keeps its
+/// address from being reused by another ranker while the key is cached.
+#[derive(Clone, Debug)]
+struct RankerKey(Arc<dyn SuggestionRanker>);
+
+impl PartialEq for RankerKey {
+    fn eq(&self, other: &RankerKey) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for RankerKey {}
+
+impl Hash for RankerKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Arc::as_ptr(&self.0) as *const ()).hash(state)
+    }
+}
+
+impl ConfigKey {
+    fn new(config: &SpellerConfig) -> ConfigKey {
+        ConfigKey {
+            n_best: config.n_best,
+            max_weight: config.max_weight.map(f32::to_bits),
+            beam: config.beam.map(f32::to_bits),
+            case_handling: config.case_handling.as_ref().map(|c| {
+                [
+                    c.start_penalty.to_bits(),
+                    c.end_penalty.to_bits(),
+                    c.mid_penalty.to_bits(),
+                ]
+            }),
+            no_suggest_tags: config.no_suggest_tags.clone(),
+            no_accept_tags: config.no_accept_tags.clone(),
+            max_iterations: config.max_iterations,
+            alignment: config.alignment,
+            split_penalty: config.split_penalty.map(f32::to_bits),
+            rankers: config.rankers.iter().cloned().map(RankerKey).collect(),
+        }
+    }
+}
+
+type CacheKey = (SmolStr, ConfigKey);
+
+/// Wraps a speller with bounded least-recently-used caches of `is_correct`
+/// and `suggest` results, keyed by the word and the configuration used.
+///
+/// Real text repeats the same misspellings and names over and over, so most
+/// lookups after the first few pages are answered from the cache. Everything
+/// else is passed through to the wrapped speller as is.
+#[derive(Debug)]
+pub struct CachedSpeller<S: ?Sized> {
+    speller: Arc<S>,
+    checks: Mutex<LruCache<CacheKey, bool>>,
+    suggestions: Mutex<LruCache<CacheKey, Vec<Suggestion>>>,
+    check_hits: AtomicUsize,
+    check_misses: AtomicUsize,
+    suggest_hits: AtomicUsize,
+    suggest_misses: AtomicUsize,
+}
+
+impl<S: Speller + ?Sized> CachedSpeller<S> {
+    /// Caches up to `check_capacity` correctness results and
+    /// `suggest_capacity` suggestion lists.
+    pub fn new(
+        speller: Arc<S>,
+        check_capacity: usize,
+        suggest_capacity: usize,
+    ) -> Arc<CachedSpeller<S>> {
+        Arc::new(CachedSpeller {
+            speller,
+            checks: Mutex::new(LruCache::new(check_capacity)),
+            suggestions: Mutex::new(LruCache::new(suggest_capacity)),
+            check_hits: AtomicUsize::new(0),
+            check_misses: AtomicUsize::new(0),
+            suggest_hits: AtomicUsize::new(0),
+            suggest_misses: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn speller(&self) -> &Arc<S> {
+        &self.speller
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            check_hits: self.check_hits.load(Ordering::Relaxed),
+            check_misses: self.check_misses.load(Ordering::Relaxed),
+            suggest_hits: self.suggest_hits.load(Ordering::Relaxed),
+            suggest_misses: self.suggest_misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Empties both caches. The counters are kept. Needed after changing the
+    /// rankers registered on the wrapped speller or its blocklist, which are
+    /// not part of the cache key.
+    pub fn clear(&self) {
+        self.checks.lock().clear();
+        self.suggestions.lock().clear();
+    }
+}
+
+impl<S> Speller for CachedSpeller<S>
+where
+    S: Speller + Send + Sync + ?Sized,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_correct_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
+        let key = (SmolStr::from(word), ConfigKey::new(config));
+
+        if let Some(is_correct) = self.checks.lock().get(&key) {
+            self.check_hits.fetch_add(1, Ordering::Relaxed);
+            return *is_correct;
+        }
+
+        self.check_misses.fetch_add(1, Ordering::Relaxed);
+        let is_correct = self.speller.clone().is_correct_with_config(word, config);
+        self.checks.lock().put(key, is_correct);
+        is_correct
+    }
+
+    /// Not cached, as only the plain answer of `is_correct` is kept.
+    fn check_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> CheckResult {
+        self.speller.clone().check_with_config(word, config)
+    }
+
+    fn suggest_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let key = (SmolStr::from(word), ConfigKey::new(config));
+
+        if let Some(suggestions) = self.suggestions.lock().get(&key) {
+            self.suggest_hits.fetch_add(1, Ordering::Relaxed);
+            return suggestions.clone();
+        }
+
+        self.suggest_misses.fetch_add(1, Ordering::Relaxed);
+        let result = self.speller.clone().suggest_detailed(word, config);
+
+        // Results cut short by the clock are not what the next lookup would get.
+        match result.stats.truncated {
+            Some(Truncation::TimeLimit) | Some(Truncation::Cancelled) => {}
+            _ => {
+                self.suggestions.lock().put(key, result.suggestions.clone());
+            }
+        }
+
+        result.suggestions
+    }
+
+    /// Always runs the search, as statistics are not cached.
+    fn suggest_detailed(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestResult {
+        self.speller.clone().suggest_detailed(word, config)
+    }
+
+    fn suggest_iter<'a>(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Box<dyn Iterator<Item = Suggestion> + 'a>
+    where
+        Self: 'a,
+    {
+        self.speller.clone().suggest_iter(word, config)
+    }
+
+    fn analyze(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        self.speller.clone().analyze(word)
+    }
+
+    fn analyze_suggestions_with_config(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.speller
+            .clone()
+            .analyze_suggestions_with_config(word, config)
+    }
+
+    /// Not cached, as the context makes repeats unlikely.
+    fn suggest_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.speller.clone().suggest_in_context(context, config)
+    }
+
+    fn complete_with_config(
+        self: Arc<Self>,
+        prefix: &str,
+        n: usize,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.speller.clone().complete_with_config(prefix, n, config)
+    }
+
+    fn suggest_joined(
+        self: Arc<Self>,
+        first: &str,
+        second: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.speller.clone().suggest_joined(first, second, config)
+    }
+}
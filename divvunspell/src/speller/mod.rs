@@ -1,17 +1,34 @@
+use std::borrow::Cow;
 use std::f32;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
+use parking_lot::{Mutex, RwLock};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
 use unic_ucd_category::GeneralCategory;
 
-use self::worker::SpellerWorker;
-use crate::speller::suggestion::Suggestion;
+use self::blocklist::Blocklist;
+use self::ngram::NgramModel;
+use self::ranker::SuggestionRanker;
+use self::worker::{SearchBuffers, SearchMode, SpellerWorker, SuggestionIter};
+use crate::speller::suggestion::{
+    CheckResult, SearchStats, SuggestResult, Suggestion, TrivialReason, Truncation,
+};
 use crate::tokenizer::case_handling::CaseHandler;
-use crate::transducer::Transducer;
-use crate::types::{SymbolNumber, Weight};
-
+use crate::tokenizer::WordContext;
+use crate::transducer::edit_distance::{EditDistanceConfig, EditDistanceTransducer};
+use crate::transducer::{SymbolTrie, Transducer};
+use crate::types::{NormalizationForm, SymbolNumber, Weight};
+
+pub mod blocklist;
+pub mod cache;
+pub mod ngram;
+pub mod personal;
+pub mod ranker;
 pub mod suggestion;
 mod worker;
 
@@ -28,7 +45,82 @@ pub struct SpellerConfig {
     pub max_weight: Option<Weight>,
     pub beam: Option<Weight>,
     pub case_handling: Option<CaseHandlingConfig>,
+    /// Initial capacity of the per-search node storage.
     pub node_pool_size: usize,
+    /// Analysis tags that keep a lexicon path from being suggested, such as
+    /// `+Use/SpellNoSugg`. A trailing `*` matches any tag with that prefix.
+    #[serde(default)]
+    pub no_suggest_tags: Vec<SmolStr>,
+    /// Analysis tags that keep a lexicon path from being accepted or
+    /// suggested, such as `+Err/Orth`. Matched like `no_suggest_tags`.
+    #[serde(default)]
+    pub no_accept_tags: Vec<SmolStr>,
+    /// Safety cutoff on the number of search nodes expanded per lookup.
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: Option<usize>,
+    /// Wall-clock budget for a suggestion lookup, like hfst-ospell's
+    /// `--time-cutoff`. The best suggestions found so far are returned once
+    /// it runs out.
+    #[serde(default)]
+    pub time_limit: Option<Duration>,
+    /// Aborts the lookup, returning the best suggestions found so far.
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
+    /// Records the error model arcs behind each suggestion in
+    /// `Suggestion::alignment`. Off by default as it slows the search down.
+    #[serde(default)]
+    pub alignment: bool,
+    /// Lets `complete` run the prefix through the error model first, so a
+    /// mistyped prefix still completes to the words it was meant to start.
+    #[serde(default)]
+    pub fuzzy_completion: bool,
+    /// How much the language model's cost counts against the speller's own
+    /// weights in `suggest_in_context`.
+    #[serde(default = "default_context_weight")]
+    pub context_weight: Weight,
+    /// Also suggests splitting the word in two accepted words, such as "the
+    /// cat" for "thecat", at this weight. Off if `None`, or if it is over
+    /// `max_weight`.
+    #[serde(default)]
+    pub split_penalty: Option<Weight>,
+    /// The weight of the joined word suggested by `suggest_joined`.
+    #[serde(default = "default_join_penalty")]
+    pub join_penalty: Weight,
+    /// Rankers run on this lookup's suggestions, after those registered on
+    /// the speller.
+    #[serde(skip)]
+    pub rankers: Vec<Arc<dyn SuggestionRanker>>,
+}
+
+const fn default_max_iterations() -> Option<usize> {
+    Some(10_000_000)
+}
+
+const fn default_context_weight() -> Weight {
+    1.0
+}
+
+const fn default_join_penalty() -> Weight {
+    5.0
+}
+
+/// A flag that can be shared with a running lookup to abort it early, for
+/// example when the user has kept typing and the result is no longer needed.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
 }
 
 impl SpellerConfig {
@@ -39,8 +131,27 @@ impl SpellerConfig {
             beam: None,
             case_handling: Some(CaseHandlingConfig::default()),
             node_pool_size: 128,
+            no_suggest_tags: Vec::new(),
+            no_accept_tags: Vec::new(),
+            max_iterations: default_max_iterations(),
+            time_limit: None,
+            cancellation: None,
+            alignment: false,
+            fuzzy_completion: false,
+            context_weight: default_context_weight(),
+            split_penalty: None,
+            join_penalty: default_join_penalty(),
+            rankers: Vec::new(),
         }
     }
+
+    #[inline(always)]
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map(|x| x.is_cancelled())
+            .unwrap_or(false)
+    }
 }
 
 impl CaseHandlingConfig {
@@ -54,21 +165,130 @@ impl CaseHandlingConfig {
 }
 
 pub trait Speller {
-    fn is_correct(self: Arc<Self>, word: &str) -> bool;
-    fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion>;
+    #[allow(clippy::wrong_self_convention)]
+    #[inline]
+    fn is_correct(self: Arc<Self>, word: &str) -> bool {
+        self.is_correct_with_config(word, &SpellerConfig::default())
+    }
+
+    fn is_correct_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool;
+
+    /// Like `is_correct`, but tells how the word was accepted: which case
+    /// variant of it the lexicon accepts and at what weight, or why it needed
+    /// no lookup at all.
+    #[inline]
+    fn check(self: Arc<Self>, word: &str) -> CheckResult {
+        self.check_with_config(word, &SpellerConfig::default())
+    }
+
+    fn check_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> CheckResult;
+
+    #[inline]
+    fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        self.suggest_with_config(word, &SpellerConfig::default())
+    }
+
     fn suggest_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion>;
+    /// Like `suggest_with_config`, but also reports statistics about the search.
+    fn suggest_detailed(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestResult;
+
+    /// Checks every word in `words`, spread over rayon's thread pool. Results
+    /// are in the same order as the words; repeated words are checked once.
+    fn check_many(self: Arc<Self>, words: &[&str], config: &SpellerConfig) -> Vec<bool>
+    where
+        Self: Send + Sync,
+    {
+        for_each_unique(&self, words, |speller, word| {
+            speller.is_correct_with_config(word, config)
+        })
+    }
+
+    /// Like `check_many`, but suggests corrections for every word.
+    fn suggest_many(self: Arc<Self>, words: &[&str], config: &SpellerConfig) -> Vec<Vec<Suggestion>>
+    where
+        Self: Send + Sync,
+    {
+        for_each_unique(&self, words, |speller, word| {
+            speller.suggest_with_config(word, config)
+        })
+    }
+
+    /// Yields suggestions one at a time, best first, as the search finds
+    /// them, so a caller can show the first ones early and stop pulling once
+    /// it has enough. With a cascade of error models, they are best first
+    /// within each model, as a model is only started once the ones before it
+    /// run dry. Case variants of the word are not tried, and as no
+    /// suggestion is held back, rankers are not run. Blocked words are still
+    /// left out.
+    fn suggest_iter<'a>(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Box<dyn Iterator<Item = Suggestion> + 'a>
+    where
+        Self: 'a;
+
+    /// Returns the lexicon output strings (lemma and tags) for every path
+    /// accepting the given word, like `hfst-ospell --analyse`.
+    fn analyze(self: Arc<Self>, word: &str) -> Vec<Suggestion>;
+    /// Suggests corrections for the given word, with the analyses of each
+    /// suggestion's lexicon paths attached.
+    #[inline]
+    fn analyze_suggestions(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        self.analyze_suggestions_with_config(word, &SpellerConfig::default())
+    }
+
+    fn analyze_suggestions_with_config(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion>;
+
+    /// Suggests corrections for the current word of `context`, ranked by the
+    /// speller's weights plus how well each suggestion fits between the words
+    /// around it, according to the speller's language model. Without a
+    /// language model this is the same as `suggest_with_config`.
+    fn suggest_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion>;
+
+    /// The `n` lightest lexicon words starting with `prefix`, such as for the
+    /// suggestion bar of an on-screen keyboard.
+    #[inline]
+    fn complete(self: Arc<Self>, prefix: &str, n: usize) -> Vec<Suggestion> {
+        self.complete_with_config(prefix, n, &SpellerConfig::default())
+    }
+
+    /// Like `complete`, with the given config. `n` takes the place of the
+    /// config's `n_best`.
+    fn complete_with_config(
+        self: Arc<Self>,
+        prefix: &str,
+        n: usize,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion>;
+
+    /// Suggests joining two adjacent words, such as "hap pen", into one
+    /// accepted word, at the config's `join_penalty`. Empty if the joined
+    /// word is not accepted either.
+    fn suggest_joined(
+        self: Arc<Self>,
+        first: &str,
+        second: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion>;
 }
 
 impl<F, T, U> Speller for HfstSpeller<F, T, U>
 where
-    F: crate::vfs::File + Send,
-    T: Transducer<F> + Send,
-    U: Transducer<F> + Send,
+    F: crate::vfs::File + Send + Sync,
+    T: Transducer<F> + Send + Sync,
+    U: Transducer<F> + Send + Sync,
 {
     #[allow(clippy::wrong_self_convention)]
-    fn is_correct(self: Arc<Self>, word: &str) -> bool {
-        use crate::tokenizer::case_handling::*;
-
+    fn is_correct_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
         if word.len() == 0 {
             return true;
         }
@@ -79,45 +299,237 @@ where
             return true;
         }
 
-        let words = word_variants(word).words;
+        self.accepts(word, config)
+    }
+
+    fn check_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> CheckResult {
+        use crate::tokenizer::case_handling::*;
+
+        if word.is_empty() {
+            return CheckResult::Trivial(TrivialReason::Empty);
+        }
+
+        if word.chars().all(|c| !GeneralCategory::of(c).is_letter()) {
+            return CheckResult::Trivial(TrivialReason::NoLetters);
+        }
+
+        for variant in word_variants(word).words.into_iter() {
+            let input = self.to_input_vec(0, &self.normalize(&variant));
+            let worker = SpellerWorker::new(self.clone(), 0, input, config.clone());
+
+            if let Some(weight) = worker.accepted_weight() {
+                return CheckResult::Accepted {
+                    exact: variant == word,
+                    variant,
+                    weight,
+                };
+            }
+        }
+
+        CheckResult::Rejected
+    }
+
+    fn suggest_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        self.suggest_with_mode(word, config, OutputMode::Surface, None)
+            .suggestions
+    }
+
+    fn suggest_detailed(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestResult {
+        self.suggest_with_mode(word, config, OutputMode::Surface, None)
+    }
+
+    fn suggest_iter<'a>(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Box<dyn Iterator<Item = Suggestion> + 'a>
+    where
+        Self: 'a,
+    {
+        if word.is_empty() {
+            return Box::new(std::iter::empty());
+        }
+
+        let speller = self.clone();
+        let form = NormalizationForm::of(word);
+        let word = self.normalize(word).into_owned();
+        let config = config.clone();
+        let n_best = config.n_best.unwrap_or(usize::MAX);
+        let mut yielded = HashSet::new();
+
+        // Later error models are only started once the earlier ones run dry.
+        let suggestions = (0..self.error_models.len())
+            .flat_map(move |error_model| {
+                let input = speller.to_input_vec(error_model, &word);
+                let worker =
+                    SpellerWorker::new(speller.clone(), error_model, input, config.clone());
+                SuggestionIter::new(worker)
+            })
+            .filter(move |suggestion| yielded.insert(suggestion.value.clone()))
+            .filter_map(move |mut suggestion| {
+                if self.blocklist.is_blocked(suggestion.value()) {
+                    return None;
+                }
+
+                self.denormalize(std::slice::from_mut(&mut suggestion), form);
+                Some(suggestion)
+            })
+            .take(n_best);
+
+        Box::new(suggestions)
+    }
+
+    fn analyze(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        use crate::tokenizer::case_handling::*;
+
+        if word.is_empty() {
+            return vec![];
+        }
 
-        for word in words.into_iter() {
+        for word in word_variants(&self.normalize(word)).words.into_iter() {
             let worker = SpellerWorker::new(
                 self.clone(),
-                self.to_input_vec(&word),
+                0,
+                self.to_input_vec(0, &word),
                 SpellerConfig::default(),
             );
 
-            if worker.is_correct() {
-                return true;
+            let analyses = worker.analyze();
+
+            if !analyses.is_empty() {
+                return analyses;
             }
         }
 
-        false
+        vec![]
     }
 
-    #[inline]
-    fn suggest(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
-        self.suggest_with_config(word, &SpellerConfig::default())
+    fn analyze_suggestions_with_config(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.suggest_with_mode(word, config, OutputMode::WithAnalyses, None)
+            .suggestions
     }
 
-    fn suggest_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+    fn suggest_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        self.suggest_with_mode(
+            &context.current.1,
+            config,
+            OutputMode::Surface,
+            Some(context),
+        )
+        .suggestions
+    }
+
+    fn complete_with_config(
+        self: Arc<Self>,
+        prefix: &str,
+        n: usize,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
         use crate::tokenizer::case_handling::*;
 
-        if word.len() == 0 {
+        if prefix.is_empty() || n == 0 {
             return vec![];
         }
 
-        if let Some(case_handling) = config.case_handling.as_ref() {
-            let case_handler = word_variants(word);
+        let mut config = config.clone();
+        config.n_best = Some(n);
+        let search_config = self.search_config(&config, false);
 
-            self.suggest_case(case_handler, config, case_handling)
-        } else {
-            self.suggest_single(word, config)
+        let form = NormalizationForm::of(prefix);
+        let normalized = self.normalize(prefix);
+        let CaseHandler {
+            mutation, words, ..
+        } = word_variants(&normalized);
+        let mut best: HashMap<SmolStr, Suggestion> = HashMap::new();
+
+        for word in words.iter() {
+            for mut completion in self.complete_single(word, &search_config) {
+                // Completions of a lowered prefix take the prefix's case.
+                if word.as_str() != normalized {
+                    completion.value = match mutation {
+                        CaseMutation::FirstCaps => upper_first(completion.value()),
+                        CaseMutation::AllCaps => upper_case(completion.value()),
+                        CaseMutation::None => completion.value,
+                    };
+                }
+
+                match best.get(&completion.value) {
+                    Some(existing) if existing.weight <= completion.weight => {}
+                    _ => {
+                        best.insert(completion.value.clone(), completion);
+                    }
+                }
+            }
         }
+
+        let mut out = best.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        self.denormalize(&mut out, form);
+        self.rank(prefix, &mut out, &config);
+        out
     }
+
+    fn suggest_joined(
+        self: Arc<Self>,
+        first: &str,
+        second: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        if first.is_empty() || second.is_empty() {
+            return vec![];
+        }
+
+        let joined = format!("{}{}", first, second);
+        let mut suggestions = vec![];
+
+        if self.accepts(&joined, config) {
+            suggestions.push(Suggestion::new(joined.into(), config.join_penalty));
+        }
+
+        self.rank(&format!("{} {}", first, second), &mut suggestions, config);
+        suggestions
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputMode {
+    /// Surface forms only, with any analysis tags stripped.
+    Surface,
+    /// Surface forms along with the lexicon analyses of each.
+    WithAnalyses,
+}
+
+/// A lexicon of the speller, with the weight added to every path through it.
+#[derive(Debug)]
+struct SpellerLexicon<U> {
+    lexicon: U,
+    weight: Weight,
+}
+
+/// An error model of the speller's cascade, with what it takes to run it:
+/// the input segmentation for its alphabet and the mapping from its symbols
+/// to those of each lexicon.
+#[derive(Debug)]
+struct SpellerErrorModel<T> {
+    mutator: T,
+    max_weight: Option<Weight>,
+    input_trie: SymbolTrie,
+    alphabet_translators: Vec<Vec<SymbolNumber>>,
 }
 
+const DEFAULT_SEARCH_POOL_SIZE: usize = 16;
+
+/// How many times `n_best` suggestions are searched for when rankers, the
+/// blocklist or the language model get to rework them.
+const RANKING_HEADROOM: usize = 4;
+
 #[derive(Debug)]
 pub struct HfstSpeller<F, T, U>
 where
@@ -125,61 +537,459 @@ where
     T: Transducer<F>,
     U: Transducer<F>,
 {
-    mutator: T,
-    lexicon: U,
-    alphabet_translator: Vec<SymbolNumber>,
+    error_models: Vec<SpellerErrorModel<T>>,
+    lexicons: Vec<SpellerLexicon<U>>,
+    search_buffers: Mutex<Vec<SearchBuffers>>,
+    search_pool_size: AtomicUsize,
+    language_model: RwLock<Option<Arc<NgramModel>>>,
+    rankers: RwLock<Vec<Arc<dyn SuggestionRanker>>>,
+    normalization: RwLock<Option<NormalizationForm>>,
+    blocklist: Blocklist,
     _file: std::marker::PhantomData<F>,
 }
 
+impl<F, U> HfstSpeller<F, EditDistanceTransducer, U>
+where
+    F: crate::vfs::File,
+    U: Transducer<F>,
+{
+    /// Builds a speller from a lexicon alone, with an edit distance error
+    /// model generated from the lexicon's alphabet. Useful for languages that
+    /// have no compiled error model yet.
+    pub fn with_edit_distance(
+        lexicon: U,
+        config: EditDistanceConfig,
+    ) -> Arc<HfstSpeller<F, EditDistanceTransducer, U>> {
+        let mutator = EditDistanceTransducer::new(lexicon.alphabet(), config);
+        HfstSpeller::new(mutator, lexicon)
+    }
+}
+
 impl<F, T, U> HfstSpeller<F, T, U>
 where
     F: crate::vfs::File,
     T: Transducer<F>,
     U: Transducer<F>,
 {
-    pub fn new(mutator: T, mut lexicon: U) -> Arc<HfstSpeller<F, T, U>> {
-        let alphabet_translator = lexicon.mut_alphabet().create_translator_from(&mutator);
+    pub fn new(mutator: T, lexicon: U) -> Arc<HfstSpeller<F, T, U>> {
+        HfstSpeller::with_lexicons(mutator, vec![(lexicon, 0.0)])
+    }
+
+    /// Builds a speller that runs the one error model against several
+    /// lexicons at once, such as a main lexicon plus a place name lexicon.
+    /// Each lexicon's weight is added to every path through it, so a domain
+    /// lexicon can be ranked below or above the main one. Suggestions from
+    /// all lexicons are merged, keeping the best weight for each word.
+    ///
+    /// Panics if `lexicons` is empty.
+    pub fn with_lexicons(mutator: T, lexicons: Vec<(U, Weight)>) -> Arc<HfstSpeller<F, T, U>> {
+        HfstSpeller::with_error_models(vec![(mutator, None)], lexicons)
+    }
+
+    /// Builds a speller with a cascade of error models, each with its own
+    /// weight limit. The models are tried in order, and a model is only run
+    /// if the ones before it found fewer than `n_best` suggestions, so a
+    /// cheap typo model can come first and an expensive one serve as a
+    /// fallback.
+    ///
+    /// Panics if `error_models` or `lexicons` is empty.
+    pub fn with_error_models(
+        error_models: Vec<(T, Option<Weight>)>,
+        lexicons: Vec<(U, Weight)>,
+    ) -> Arc<HfstSpeller<F, T, U>> {
+        assert!(
+            !error_models.is_empty(),
+            "a speller needs at least one error model"
+        );
+        assert!(!lexicons.is_empty(), "a speller needs at least one lexicon");
+
+        let mut lexicons = lexicons
+            .into_iter()
+            .map(|(lexicon, weight)| SpellerLexicon { lexicon, weight })
+            .collect::<Vec<_>>();
+
+        // Before the error models' symbols are added to the alphabet.
+        let normalization = lexicons[0].lexicon.alphabet().normalization_form();
+
+        let error_models = error_models
+            .into_iter()
+            .map(|(mutator, max_weight)| {
+                let alphabet_translators = lexicons
+                    .iter_mut()
+                    .map(|x| x.lexicon.mut_alphabet().create_translator_from(&mutator))
+                    .collect();
+                let input_trie = SymbolTrie::new(mutator.alphabet());
+
+                SpellerErrorModel {
+                    mutator,
+                    max_weight,
+                    input_trie,
+                    alphabet_translators,
+                }
+            })
+            .collect();
 
         Arc::new(HfstSpeller {
-            mutator,
-            lexicon,
-            alphabet_translator,
+            error_models,
+            lexicons,
+            search_buffers: Mutex::new(Vec::new()),
+            search_pool_size: AtomicUsize::new(DEFAULT_SEARCH_POOL_SIZE),
+            language_model: RwLock::new(None),
+            rankers: RwLock::new(Vec::new()),
+            normalization: RwLock::new(normalization),
+            blocklist: Blocklist::new(),
             _file: std::marker::PhantomData::<F>,
         })
     }
 
+    /// Sets how many sets of search storage the speller keeps for reuse once
+    /// a lookup is done, 16 by default. Zero allocates afresh for every
+    /// lookup.
+    pub fn set_search_pool_size(&self, size: usize) {
+        self.search_pool_size.store(size, Ordering::Relaxed);
+        self.search_buffers.lock().truncate(size);
+    }
+
+    pub fn search_pool_size(&self) -> usize {
+        self.search_pool_size.load(Ordering::Relaxed)
+    }
+
+    /// Sets the language model `suggest_in_context` ranks suggestions with,
+    /// or removes it.
+    pub fn set_language_model(&self, model: Option<Arc<NgramModel>>) {
+        *self.language_model.write() = model;
+    }
+
+    pub fn language_model(&self) -> Option<Arc<NgramModel>> {
+        self.language_model.read().clone()
+    }
+
+    /// Registers a ranker to run on every word's suggestions and
+    /// completions, after any registered before it. `suggest_iter` is the
+    /// exception, as it yields suggestions before the search is done.
+    pub fn add_ranker(&self, ranker: Arc<dyn SuggestionRanker>) {
+        self.rankers.write().push(ranker);
+    }
+
+    pub fn clear_rankers(&self) {
+        self.rankers.write().clear();
+    }
+
+    /// Sets the normalization form words are put in before they are looked
+    /// up, in place of the one detected from the lexicon's alphabet, or turns
+    /// normalization off.
+    pub fn set_normalization(&self, form: Option<NormalizationForm>) {
+        *self.normalization.write() = form;
+    }
+
+    pub fn normalization(&self) -> Option<NormalizationForm> {
+        *self.normalization.read()
+    }
+
+    /// Words never to be suggested. Entries can be added at any time, though
+    /// a `CachedSpeller` wrapping the speller has to be cleared to forget
+    /// suggestions it cached before.
+    pub fn blocklist(&self) -> &Blocklist {
+        &self.blocklist
+    }
+
+    /// The first error model of the cascade.
     pub fn mutator(&self) -> &T {
-        &self.mutator
+        &self.error_models[0].mutator
     }
 
+    pub fn mutators(&self) -> impl Iterator<Item = &T> {
+        self.error_models.iter().map(|x| &x.mutator)
+    }
+
+    /// The first, or main, lexicon.
     pub fn lexicon(&self) -> &U {
-        &self.lexicon
+        &self.lexicons[0].lexicon
     }
 
-    fn alphabet_translator(&self) -> &Vec<SymbolNumber> {
-        &self.alphabet_translator
+    pub fn lexicons(&self) -> impl Iterator<Item = &U> {
+        self.lexicons.iter().map(|x| &x.lexicon)
     }
 
-    fn to_input_vec(&self, word: &str) -> Vec<SymbolNumber> {
-        let alphabet = self.mutator().alphabet();
-        let key_table = alphabet.key_table();
+    fn mutator_at(&self, index: usize) -> &T {
+        &self.error_models[index].mutator
+    }
+
+    fn error_model_max_weight(&self, index: usize) -> Option<Weight> {
+        self.error_models[index].max_weight
+    }
 
-        word.chars()
-            .map(|ch| {
-                let s = ch.to_string();
-                key_table
-                    .iter()
-                    .position(|x| x == &s)
-                    .map(|x| x as u16)
-                    .unwrap_or_else(|| alphabet.unknown().unwrap_or(0u16))
+    fn lexicon_at(&self, index: usize) -> &U {
+        &self.lexicons[index].lexicon
+    }
+
+    fn lexicon_weights(&self) -> impl Iterator<Item = Weight> + '_ {
+        self.lexicons.iter().map(|x| x.weight)
+    }
+
+    fn alphabet_translator(&self, error_model: usize, lexicon: usize) -> &[SymbolNumber] {
+        &self.error_models[error_model].alphabet_translators[lexicon]
+    }
+
+    fn to_input_vec(&self, error_model: usize, word: &str) -> Vec<SymbolNumber> {
+        let error_model = &self.error_models[error_model];
+        let unknown = error_model.mutator.alphabet().unknown().unwrap_or(0u16);
+        error_model.input_trie.segment(word, unknown)
+    }
+
+    /// Suggests corrections for `word`. Given the context the word is in,
+    /// the language model's cost of each suggestion there is added to its
+    /// weight before the suggestions are ranked.
+    fn suggest_with_mode(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+        mode: OutputMode,
+        context: Option<&WordContext>,
+    ) -> SuggestResult {
+        use crate::tokenizer::case_handling::*;
+
+        if word.len() == 0 {
+            return SuggestResult::default();
+        }
+
+        let form = NormalizationForm::of(word);
+        let normalized = self.normalize(word);
+
+        let model = context.and_then(|_| self.language_model());
+        let deadline = config.time_limit.map(|limit| Instant::now() + limit);
+        let search_config = self.search_config(config, model.is_some());
+
+        let mut result = if let Some(case_handling) = config.case_handling.as_ref() {
+            let case_handler = word_variants(&normalized);
+
+            self.clone()
+                .suggest_case(case_handler, &search_config, case_handling)
+        } else {
+            self.clone().suggest_single(&normalized, &search_config)
+        };
+
+        let split_penalty = config
+            .split_penalty
+            .filter(|penalty| config.max_weight.map(|max| *penalty <= max).unwrap_or(true));
+
+        // Splitting looks up both halves at every position, so it is not
+        // started once the search has run out of time or been cancelled.
+        if let (Some(penalty), None) = (split_penalty, result.stats.truncated) {
+            let splits = self.split_suggestions(
+                &normalized,
+                penalty,
+                &search_config,
+                deadline,
+                &mut result.stats,
+            );
+
+            for split in splits {
+                if !result.suggestions.iter().any(|x| x.value == split.value) {
+                    result.suggestions.push(split);
+                }
+            }
+        }
+
+        self.denormalize(&mut result.suggestions, form);
+
+        if let (Some(context), Some(model)) = (context, model) {
+            for suggestion in result.suggestions.iter_mut() {
+                suggestion.weight +=
+                    config.context_weight * model.context_cost(context, suggestion.value());
+            }
+        }
+
+        self.rank(word, &mut result.suggestions, config);
+
+        if mode == OutputMode::WithAnalyses {
+            for suggestion in result.suggestions.iter_mut() {
+                suggestion.analyses = self.suggestion_analyses(suggestion.value(), config);
+            }
+        }
+
+        result
+    }
+
+    /// The config to search with for suggestions that go through `rank`.
+    /// When rankers, the blocklist or, `in_context`, the language model may
+    /// drop or demote some of them, the search keeps `RANKING_HEADROOM` times
+    /// `n_best`, so that there are others to take their place once `rank`
+    /// truncates.
+    fn search_config<'a>(
+        &self,
+        config: &'a SpellerConfig,
+        in_context: bool,
+    ) -> Cow<'a, SpellerConfig> {
+        let reranked = in_context
+            || !config.rankers.is_empty()
+            || !self.rankers.read().is_empty()
+            || !self.blocklist.is_empty();
+
+        match config.n_best {
+            Some(n_best) if reranked => {
+                let mut config = config.clone();
+                config.n_best = Some(n_best.saturating_mul(RANKING_HEADROOM));
+                Cow::Owned(config)
+            }
+            _ => Cow::Borrowed(config),
+        }
+    }
+
+    /// Runs the registered rankers and then the config's on the suggestions
+    /// for `word`, drops any blocked ones, and sorts and truncates what is
+    /// left to the config's `n_best`.
+    fn rank(&self, word: &str, suggestions: &mut Vec<Suggestion>, config: &SpellerConfig) {
+        for ranker in self.rankers.read().iter().chain(config.rankers.iter()) {
+            ranker.rank(word, suggestions);
+        }
+
+        self.blocklist.retain(suggestions);
+
+        suggestions.sort();
+        if let Some(n_best) = config.n_best {
+            suggestions.truncate(n_best);
+        }
+    }
+
+    /// `word` in the lexicon's normalization form.
+    fn normalize<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        match self.normalization() {
+            Some(form) => form.normalize(word),
+            None => Cow::Borrowed(word),
+        }
+    }
+
+    /// Puts suggestions from the lexicon back into `form`, the one the word
+    /// was typed in.
+    fn denormalize(&self, suggestions: &mut [Suggestion], form: Option<NormalizationForm>) {
+        let form = match form {
+            Some(form) if Some(form) != self.normalization() => form,
+            _ => return,
+        };
+
+        for suggestion in suggestions.iter_mut() {
+            if let Cow::Owned(value) = form.normalize(&suggestion.value) {
+                suggestion.value = value.into();
+            }
+        }
+    }
+
+    /// Whether the lexicon accepts the word or one of its case variants.
+    fn accepts(self: &Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
+        use crate::tokenizer::case_handling::*;
+
+        word_variants(&self.normalize(word))
+            .words
+            .into_iter()
+            .any(|word| {
+                let worker = SpellerWorker::new(
+                    self.clone(),
+                    0,
+                    self.to_input_vec(0, &word),
+                    config.clone(),
+                );
+                worker.is_correct()
             })
-            .collect()
     }
 
-    fn suggest_single(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
-        let worker = SpellerWorker::new(self.clone(), self.to_input_vec(word), config.clone());
+    /// Every way of splitting `word` in two accepted words with letters in
+    /// both, as suggestions at `penalty`. Stops early, noting why in `stats`,
+    /// once the lookup is out of time or cancelled.
+    fn split_suggestions(
+        self: &Arc<Self>,
+        word: &str,
+        penalty: Weight,
+        config: &SpellerConfig,
+        deadline: Option<Instant>,
+        stats: &mut SearchStats,
+    ) -> Vec<Suggestion> {
+        let has_letter = |s: &str| s.chars().any(|c| GeneralCategory::of(c).is_letter());
+        let mut splits = vec![];
+
+        for (i, _) in word.char_indices().skip(1) {
+            let (left, right) = word.split_at(i);
+
+            if !has_letter(left) || !has_letter(right) {
+                continue;
+            }
+
+            let split_config = match remaining_budget(config, deadline) {
+                Ok(split_config) => split_config,
+                Err(reason) => {
+                    stats.truncated = Some(reason);
+                    break;
+                }
+            };
+
+            if self.accepts(left, &split_config) && self.accepts(right, &split_config) {
+                splits.push(Suggestion::new(
+                    format!("{} {}", left, right).into(),
+                    penalty,
+                ));
+            }
+        }
 
-        worker.suggest()
+        splits
+    }
+
+    /// Suggests corrections for one form of the word, going down the cascade
+    /// of error models until they have found `n_best` suggestions between
+    /// them. The suggestions are left unsorted and untruncated for the
+    /// rankers.
+    fn suggest_single(self: &Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestResult {
+        let mut best: HashMap<SmolStr, Suggestion> = HashMap::new();
+        let mut stats = SearchStats::default();
+        let deadline = config.time_limit.map(|limit| Instant::now() + limit);
+
+        for error_model in 0..self.error_models.len() {
+            if config.n_best.map(|n| best.len() >= n).unwrap_or(false) {
+                break;
+            }
+
+            // All error models share the one time budget.
+            let model_config = match remaining_budget(config, deadline) {
+                Ok(model_config) => model_config,
+                Err(reason) => {
+                    stats.truncated = Some(reason);
+                    break;
+                }
+            };
+
+            let input = self.to_input_vec(error_model, word);
+            let worker = SpellerWorker::new(self.clone(), error_model, input, model_config);
+            let result = SuggestionIter::new(worker).into_result();
+            stats.merge(&result.stats);
+
+            for suggestion in result.suggestions {
+                match best.get(&suggestion.value) {
+                    Some(existing) if existing.weight <= suggestion.weight => {}
+                    _ => {
+                        best.insert(suggestion.value.clone(), suggestion);
+                    }
+                }
+            }
+        }
+
+        let suggestions = best.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        SuggestResult { suggestions, stats }
+    }
+
+    /// Lexicon analyses of a suggested word, best first.
+    fn suggestion_analyses(self: &Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<SmolStr> {
+        use crate::tokenizer::case_handling::*;
+
+        for word in word_variants(&self.normalize(word)).words.into_iter() {
+            let worker =
+                SpellerWorker::new(self.clone(), 0, self.to_input_vec(0, &word), config.clone());
+            let analyses = worker.analyze_suggestion();
+
+            if !analyses.is_empty() {
+                return analyses.into_iter().map(|x| x.value).collect();
+            }
+        }
+
+        vec![]
     }
 
     fn suggest_case(
@@ -187,7 +997,7 @@ where
         case: CaseHandler,
         config: &SpellerConfig,
         case_handling: &CaseHandlingConfig,
-    ) -> Vec<Suggestion> {
+    ) -> SuggestResult {
         use crate::tokenizer::case_handling::*;
         use crate::tokenizer::case_handling::{CaseMode, CaseMutation};
 
@@ -196,11 +1006,23 @@ where
             mode,
             words,
         } = case;
-        let mut best: HashMap<SmolStr, f32> = HashMap::new();
+        let mut best: HashMap<SmolStr, Suggestion> = HashMap::new();
+        let mut stats = SearchStats::default();
+        let deadline = config.time_limit.map(|limit| Instant::now() + limit);
 
         for word in words.iter() {
-            let worker = SpellerWorker::new(self.clone(), self.to_input_vec(&word), config.clone());
-            let mut suggestions = worker.suggest();
+            // All case variants share the one time budget.
+            let variant_config = match remaining_budget(config, deadline) {
+                Ok(variant_config) => variant_config,
+                Err(reason) => {
+                    stats.truncated = Some(reason);
+                    break;
+                }
+            };
+
+            let result = self.suggest_single(&word, &variant_config);
+            let mut suggestions = result.suggestions;
+            stats.merge(&result.stats);
 
             match mutation {
                 CaseMutation::FirstCaps => {
@@ -237,44 +1059,142 @@ where
                                 + strsim::damerau_levenshtein(&word.as_str(), sugg.value());
                         let penalty_middle = case_handling.mid_penalty * distance as f32;
                         let additional_weight = penalty_start + penalty_end + penalty_middle;
+                        let weight = sugg.weight + additional_weight;
 
-                        best.entry(sugg.value.clone())
-                            .and_modify(|entry| {
-                                let weight = sugg.weight + additional_weight;
-                                if entry as &_ > &weight {
-                                    *entry = weight
+                        match best.get_mut(&sugg.value) {
+                            Some(entry) => {
+                                if entry.weight > weight {
+                                    entry.weight = weight;
+                                    entry.alignment = sugg.alignment;
                                 }
-                            })
-                            .or_insert(sugg.weight + additional_weight);
+                            }
+                            None => {
+                                best.insert(sugg.value.clone(), Suggestion { weight, ..sugg });
+                            }
+                        }
                     }
                 }
                 CaseMode::FirstResults => {
                     if !suggestions.is_empty() {
-                        return suggestions;
+                        return SuggestResult { suggestions, stats };
                     }
                 }
             }
         }
 
-        if best.is_empty() {
-            return vec![];
+        SuggestResult {
+            suggestions: best.into_iter().map(|(_, v)| v).collect(),
+            stats,
         }
+    }
+}
 
-        let mut out = best
-            .into_iter()
-            .map(|(k, v)| Suggestion {
-                value: k,
-                weight: v,
-            })
-            .collect::<Vec<_>>();
-        out.sort();
-        if let Some(n_best) = config.n_best {
-            out.truncate(n_best);
+impl<F, T, U> HfstSpeller<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    /// Completes one form of the prefix. A fuzzy completion goes down the
+    /// cascade of error models like `suggest_single` does.
+    fn complete_single(self: &Arc<Self>, prefix: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let error_models = if config.fuzzy_completion {
+            self.error_models.len()
+        } else {
+            1
+        };
+        let mode = if config.fuzzy_completion {
+            SearchMode::FuzzyComplete
+        } else {
+            SearchMode::Complete
+        };
+        let n_best = config.n_best.unwrap_or(usize::MAX);
+        let mut best: HashMap<SmolStr, Suggestion> = HashMap::new();
+
+        for error_model in 0..error_models {
+            if best.len() >= n_best {
+                break;
+            }
+
+            let input = self.to_input_vec(error_model, prefix);
+
+            // Without an unknown symbol, characters missing from the alphabet
+            // come out as epsilons, which only an error model can make sense of.
+            if mode == SearchMode::Complete && input.contains(&0) {
+                break;
+            }
+
+            let worker = SpellerWorker::new(self.clone(), error_model, input, config.clone());
+
+            for completion in SuggestionIter::with_mode(worker, mode) {
+                match best.get(&completion.value) {
+                    Some(existing) if existing.weight <= completion.weight => {}
+                    _ => {
+                        best.insert(completion.value.clone(), completion);
+                    }
+                }
+            }
         }
+
+        let mut out = best.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+        out.sort();
+        out.truncate(n_best);
         out
     }
 }
 
+/// The config for the next step of a lookup whose steps share one time
+/// budget, or the reason the lookup has to stop here.
+fn remaining_budget(
+    config: &SpellerConfig,
+    deadline: Option<Instant>,
+) -> Result<SpellerConfig, Truncation> {
+    let mut config = config.clone();
+
+    if let Some(deadline) = deadline {
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(Truncation::TimeLimit);
+        }
+        config.time_limit = Some(deadline - now);
+    }
+
+    if config.is_cancelled() {
+        return Err(Truncation::Cancelled);
+    }
+
+    Ok(config)
+}
+
+/// Runs `f` once for each distinct word on rayon's thread pool, then
+/// hands the results back in the order of `words`.
+fn for_each_unique<S, R, G>(speller: &Arc<S>, words: &[&str], f: G) -> Vec<R>
+where
+    S: ?Sized + Send + Sync,
+    R: Clone + Send,
+    G: Fn(Arc<S>, &str) -> R + Sync,
+{
+    let mut index = HashMap::new();
+    let mut unique = vec![];
+
+    let slots = words
+        .iter()
+        .map(|word| {
+            *index.entry(*word).or_insert_with(|| {
+                unique.push(*word);
+                unique.len() - 1
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let results = unique
+        .par_iter()
+        .map(|word| f(speller.clone(), word))
+        .collect::<Vec<_>>();
+
+    slots.into_iter().map(|i| results[i].clone()).collect()
+}
+
 #[cfg(feature = "internal_ffi")]
 pub(crate) mod ffi {
     use super::*;
@@ -303,6 +1223,8 @@ pub(crate) mod ffi {
         pub beam: Weight,
         pub case_handling: FfiCaseHandlingConfig,
         pub node_pool_size: usize,
+        /// In milliseconds, or 0 for no limit.
+        pub time_limit_ms: u64,
     }
 
     pub struct SpellerConfigMarshaler;
@@ -338,6 +1260,10 @@ pub(crate) mod ffi {
                 beam: config.beam.unwrap_or(0.0),
                 case_handling,
                 node_pool_size: config.node_pool_size,
+                time_limit_ms: config
+                    .time_limit
+                    .map(|limit| limit.as_millis() as u64)
+                    .unwrap_or(0),
             };
 
             Ok(Box::into_raw(Box::new(out)) as *const _)
@@ -383,6 +1309,12 @@ pub(crate) mod ffi {
                 },
                 case_handling,
                 node_pool_size: config.node_pool_size,
+                time_limit: if config.time_limit_ms > 0 {
+                    Some(Duration::from_millis(config.time_limit_ms))
+                } else {
+                    None
+                },
+                ..SpellerConfig::default()
             };
 
             Ok(out)
@@ -414,6 +1346,17 @@ pub(crate) mod ffi {
         speller.suggest_with_config(word, &config)
     }
 
+    #[cffi::marshal(return_marshaler = "SuggestionVecMarshaler")]
+    pub extern "C" fn divvun_thfst_box_speller_suggest_in_context(
+        #[marshal(cffi::ArcRefMarshaler::<ThfstBoxSpeller>)] speller: Arc<ThfstBoxSpeller>,
+        #[marshal(cffi::StrMarshaler)] first_half: &str,
+        #[marshal(cffi::StrMarshaler)] second_half: &str,
+        #[marshal(SpellerConfigMarshaler)] config: SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let context = crate::tokenizer::cursor_context(first_half, second_half);
+        speller.suggest_in_context(&context, &config)
+    }
+
     #[cffi::marshal]
     pub extern "C" fn divvun_thfst_chunked_box_speller_is_correct(
         #[marshal(cffi::ArcRefMarshaler::<ThfstChunkedBoxSpeller>)] speller: Arc<
@@ -445,6 +1388,19 @@ pub(crate) mod ffi {
         speller.suggest_with_config(word, &config)
     }
 
+    #[cffi::marshal(return_marshaler = "SuggestionVecMarshaler")]
+    pub extern "C" fn divvun_thfst_chunked_box_speller_suggest_in_context(
+        #[marshal(cffi::ArcRefMarshaler::<ThfstChunkedBoxSpeller>)] speller: Arc<
+            ThfstChunkedBoxSpeller,
+        >,
+        #[marshal(cffi::StrMarshaler)] first_half: &str,
+        #[marshal(cffi::StrMarshaler)] second_half: &str,
+        #[marshal(SpellerConfigMarshaler)] config: SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let context = crate::tokenizer::cursor_context(first_half, second_half);
+        speller.suggest_in_context(&context, &config)
+    }
+
     #[cffi::marshal]
     pub extern "C" fn divvun_hfst_zip_speller_is_correct(
         #[marshal(cffi::ArcRefMarshaler::<HfstZipSpeller>)] speller: Arc<HfstZipSpeller>,
@@ -470,6 +1426,17 @@ pub(crate) mod ffi {
         speller.suggest_with_config(word, &config)
     }
 
+    #[cffi::marshal(return_marshaler = "SuggestionVecMarshaler")]
+    pub extern "C" fn divvun_hfst_zip_speller_suggest_in_context(
+        #[marshal(cffi::ArcRefMarshaler::<HfstZipSpeller>)] speller: Arc<HfstZipSpeller>,
+        #[marshal(cffi::StrMarshaler)] first_half: &str,
+        #[marshal(cffi::StrMarshaler)] second_half: &str,
+        #[marshal(SpellerConfigMarshaler)] config: SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let context = crate::tokenizer::cursor_context(first_half, second_half);
+        speller.suggest_in_context(&context, &config)
+    }
+
     // Suggestions vec
 
     #[cffi::marshal]
@@ -487,3 +1454,239 @@ pub(crate) mod ffi {
         suggestions[index].value().to_string()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transducer::memory::MemoryTransducer;
+
+    type TestSpeller = HfstSpeller<std::fs::File, EditDistanceTransducer, MemoryTransducer>;
+
+    fn speller(words: &[(&str, Weight)]) -> Arc<TestSpeller> {
+        HfstSpeller::with_edit_distance(
+            MemoryTransducer::acceptor(words),
+            EditDistanceConfig::default(),
+        )
+    }
+
+    /// No case variants, so weights are the search's own.
+    fn config() -> SpellerConfig {
+        SpellerConfig {
+            case_handling: None,
+            ..SpellerConfig::default()
+        }
+    }
+
+    fn values(suggestions: &[Suggestion]) -> Vec<(&str, Weight)> {
+        suggestions
+            .iter()
+            .map(|x| (x.value.as_str(), x.weight))
+            .collect()
+    }
+
+    #[test]
+    fn suggestions_come_lightest_first() {
+        let speller = speller(&[("cat", 3.0), ("cut", 1.0), ("cot", 2.0), ("act", 0.5)]);
+
+        assert_eq!(
+            values(&speller.clone().suggest_with_config("cst", &config())),
+            vec![("cut", 2.0), ("act", 2.5), ("cot", 3.0), ("cat", 4.0)]
+        );
+
+        let iter = speller.clone().suggest_iter("cst", &config());
+        assert_eq!(
+            iter.map(|x| x.value.to_string()).collect::<Vec<_>>(),
+            vec!["cut", "act", "cot", "cat"]
+        );
+
+        let config = SpellerConfig {
+            n_best: Some(2),
+            ..config()
+        };
+        assert_eq!(
+            values(&speller.suggest_with_config("cst", &config)),
+            vec![("cut", 2.0), ("act", 2.5)]
+        );
+    }
+
+    #[test]
+    fn equivalent_paths_are_pruned() {
+        // Two epsilon arcs of different weight lead to the same state, so
+        // every node past the heavier one duplicates one past the lighter.
+        let mutator = MemoryTransducer::new(
+            &["a", "b"],
+            &[
+                (0, 0, "a", "a", 0.0),
+                (0, 0, "b", "b", 0.0),
+                (0, 1, "", "", 1.0),
+                (0, 1, "", "", 2.0),
+                (1, 1, "a", "a", 0.0),
+                (1, 1, "b", "b", 0.0),
+            ],
+            &[(0, 0.0), (1, 0.0)],
+        );
+        let lexicon = MemoryTransducer::acceptor(&[("ab", 0.0)]);
+        let speller: Arc<HfstSpeller<std::fs::File, _, _>> = HfstSpeller::new(mutator, lexicon);
+
+        let result = speller.suggest_detailed("ab", &config());
+        assert_eq!(values(&result.suggestions), vec![("ab", 0.0)]);
+        assert!(result.stats.pruned > 0);
+    }
+
+    #[test]
+    fn error_model_flags_limit_edits() {
+        // A substitution has to get past @D.EDIT@, and then sets EDIT, so
+        // there can only be one.
+        let mutator = MemoryTransducer::new(
+            &["a", "b", "@D.EDIT@", "@P.EDIT.ON@"],
+            &[
+                (0, 0, "a", "a", 0.0),
+                (0, 0, "b", "b", 0.0),
+                (0, 1, "@D.EDIT@", "@D.EDIT@", 0.0),
+                (1, 2, "@P.EDIT.ON@", "@P.EDIT.ON@", 0.0),
+                (2, 0, "a", "b", 1.0),
+                (2, 0, "b", "a", 1.0),
+            ],
+            &[(0, 0.0)],
+        );
+        let lexicon = MemoryTransducer::acceptor(&[("bb", 0.0)]);
+        let speller: Arc<HfstSpeller<std::fs::File, _, _>> = HfstSpeller::new(mutator, lexicon);
+
+        assert_eq!(
+            values(&speller.clone().suggest_with_config("ab", &config())),
+            vec![("bb", 1.0)]
+        );
+        assert!(speller.suggest_with_config("aa", &config()).is_empty());
+    }
+
+    #[test]
+    fn error_model_cascade_falls_back() {
+        let lexicon = MemoryTransducer::acceptor(&[("cat", 0.0), ("cut", 0.0), ("act", 0.0)]);
+        let alphabet = Transducer::<std::fs::File>::alphabet(&lexicon);
+        let cheap = EditDistanceTransducer::new(alphabet, EditDistanceConfig::default());
+        let expensive = EditDistanceTransducer::new(
+            alphabet,
+            EditDistanceConfig {
+                insertion: 3.0,
+                deletion: 3.0,
+                substitution: 3.0,
+                transposition: Some(3.0),
+                ..EditDistanceConfig::default()
+            },
+        );
+        let speller: Arc<TestSpeller> = HfstSpeller::with_error_models(
+            vec![(cheap, Some(1.0)), (expensive, None)],
+            vec![(lexicon, 0.0)],
+        );
+
+        // The first model finds enough within its limit.
+        let config = SpellerConfig {
+            n_best: Some(2),
+            ..config()
+        };
+        assert_eq!(
+            values(&speller.clone().suggest_with_config("cst", &config)),
+            vec![("cat", 1.0), ("cut", 1.0)]
+        );
+
+        // "act" is two edits away, over the first model's limit, so only the
+        // second model finds it.
+        let config = SpellerConfig {
+            n_best: Some(3),
+            ..config
+        };
+        assert_eq!(
+            values(&speller.suggest_with_config("cst", &config)),
+            vec![("cat", 1.0), ("cut", 1.0), ("act", 6.0)]
+        );
+    }
+
+    #[test]
+    fn completes_prefixes() {
+        let speller = speller(&[
+            ("cat", 1.0),
+            ("catalog", 3.0),
+            ("category", 2.0),
+            ("cattle", 4.0),
+            ("dog", 0.0),
+        ]);
+
+        assert_eq!(
+            values(&speller.clone().complete_with_config("cat", 3, &config())),
+            vec![("cat", 1.0), ("category", 2.0), ("catalog", 3.0)]
+        );
+        assert!(speller
+            .clone()
+            .complete_with_config("cst", 3, &config())
+            .is_empty());
+
+        let config = SpellerConfig {
+            fuzzy_completion: true,
+            ..config()
+        };
+        assert_eq!(
+            values(&speller.complete_with_config("cst", 3, &config)),
+            vec![("cat", 2.0), ("category", 3.0), ("catalog", 4.0)]
+        );
+    }
+
+    #[test]
+    fn splits_and_joins_words() {
+        let speller = speller(&[("the", 0.0), ("cat", 0.0), ("happen", 0.0)]);
+
+        assert!(speller
+            .clone()
+            .suggest_with_config("thecat", &config())
+            .is_empty());
+
+        let config = SpellerConfig {
+            split_penalty: Some(2.0),
+            ..config()
+        };
+        assert_eq!(
+            values(&speller.clone().suggest_with_config("thecat", &config)),
+            vec![("the cat", 2.0)]
+        );
+
+        assert_eq!(
+            values(&speller.clone().suggest_joined("hap", "pen", &config)),
+            vec![("happen", config.join_penalty)]
+        );
+        assert!(speller.suggest_joined("the", "cat", &config).is_empty());
+    }
+
+    #[test]
+    fn check_tells_how_a_word_was_accepted() {
+        let speller = speller(&[("cat", 0.5)]);
+
+        assert_eq!(
+            speller.clone().check("cat"),
+            CheckResult::Accepted {
+                variant: "cat".into(),
+                exact: true,
+                weight: 0.5,
+            }
+        );
+
+        let recased = speller.clone().check("CAT");
+        assert_eq!(
+            recased,
+            CheckResult::Accepted {
+                variant: "cat".into(),
+                exact: false,
+                weight: 0.5,
+            }
+        );
+        assert!(recased.is_recased());
+
+        assert_eq!(
+            speller.clone().check(""),
+            CheckResult::Trivial(TrivialReason::Empty)
+        );
+        assert_eq!(
+            speller.clone().check("1984"),
+            CheckResult::Trivial(TrivialReason::NoLetters)
+        );
+        assert_eq!(speller.check("dog"), CheckResult::Rejected);
+    }
+}
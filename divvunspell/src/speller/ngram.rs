@@ -0,0 +1,263 @@
+use std::io::{self, BufRead};
+
+use hashbrown::HashMap;
+use smol_str::SmolStr;
+
+use crate::tokenizer::case_handling::lower_case;
+use crate::tokenizer::WordContext;
+use crate::types::Weight;
+
+const SENTENCE_START: &str = "<s>";
+const UNKNOWN_WORD: &str = "<unk>";
+
+#[derive(Debug, Clone, Copy)]
+struct NgramEntry {
+    log_prob: f32,
+    backoff: f32,
+}
+
+/// A backoff n-gram language model, read from the ARPA text format.
+///
+/// Words are interned, so each n-gram is stored as a short list of word ids.
+/// Probabilities are base 10 logarithms, as in the file.
+#[derive(Debug)]
+pub struct NgramModel {
+    order: usize,
+    words: HashMap<SmolStr, u32>,
+    ngrams: HashMap<Box<[u32]>, NgramEntry>,
+    /// Log probability of words the model has never seen.
+    unknown_log_prob: f32,
+}
+
+impl NgramModel {
+    /// Reads a model in the ARPA format written by SRILM, KenLM and most
+    /// other language model toolkits.
+    pub fn from_arpa<R: BufRead>(reader: R) -> io::Result<NgramModel> {
+        let mut model = NgramModel {
+            order: 0,
+            words: HashMap::new(),
+            ngrams: HashMap::new(),
+            unknown_log_prob: 0.0,
+        };
+        let mut section = 0;
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with("ngram ") || line == "\\data\\" {
+                continue;
+            }
+
+            if line == "\\end\\" {
+                break;
+            }
+
+            if let Some(header) = line.strip_prefix('\\') {
+                section = header
+                    .strip_suffix("-grams:")
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .ok_or_else(|| invalid_line(i, line))?;
+                model.order = model.order.max(section);
+                continue;
+            }
+
+            if section == 0 {
+                return Err(invalid_line(i, line));
+            }
+
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+
+            if fields.len() != section + 1 && fields.len() != section + 2 {
+                return Err(invalid_line(i, line));
+            }
+
+            let log_prob = fields[0]
+                .parse::<f32>()
+                .map_err(|_| invalid_line(i, line))?;
+            let backoff = match fields.get(section + 1) {
+                Some(value) => value.parse::<f32>().map_err(|_| invalid_line(i, line))?,
+                None => 0.0,
+            };
+
+            let key = fields[1..=section]
+                .iter()
+                .map(|word| model.intern(word))
+                .collect::<Box<[u32]>>();
+            model.ngrams.insert(key, NgramEntry { log_prob, backoff });
+        }
+
+        // Without an <unk> entry, unseen words count as the rarest known one.
+        let unknown = model
+            .words
+            .get(UNKNOWN_WORD)
+            .and_then(|id| model.ngrams.get(&[*id][..]));
+        model.unknown_log_prob = match unknown {
+            Some(entry) => entry.log_prob,
+            None => model
+                .ngrams
+                .iter()
+                .filter(|(key, _)| key.len() == 1)
+                .map(|(_, entry)| entry.log_prob)
+                .fold(0.0, f32::min),
+        };
+
+        Ok(model)
+    }
+
+    /// The length of the longest n-grams in the model.
+    pub fn order(&self) -> usize {
+        self.order
+    }
+
+    fn intern(&mut self, word: &str) -> u32 {
+        let next = self.words.len() as u32;
+        *self.words.entry(SmolStr::from(word)).or_insert(next)
+    }
+
+    /// The id of a word, trying its lower case form if the word itself is
+    /// unknown.
+    fn word_id(&self, word: &str) -> Option<u32> {
+        self.words
+            .get(word)
+            .or_else(|| self.words.get(&lower_case(word)))
+            .copied()
+    }
+
+    /// The log probability of `word` following `history`, oldest word first,
+    /// backing off to shorter histories as needed.
+    fn log_prob(&self, history: &[Option<u32>], word: Option<u32>) -> f32 {
+        let word = match word {
+            Some(word) => word,
+            None => return self.unknown_log_prob,
+        };
+
+        // Only the most recent known words can be part of an n-gram.
+        let known = history
+            .iter()
+            .rev()
+            .take(self.order.saturating_sub(1))
+            .take_while(|id| id.is_some())
+            .count();
+        let history = history[history.len() - known..]
+            .iter()
+            .map(|id| id.unwrap())
+            .collect::<Vec<_>>();
+
+        let mut backoff = 0.0;
+        let mut key = Vec::with_capacity(history.len() + 1);
+
+        for start in 0..=history.len() {
+            key.clear();
+            key.extend_from_slice(&history[start..]);
+            key.push(word);
+
+            if let Some(entry) = self.ngrams.get(&key[..]) {
+                return backoff + entry.log_prob;
+            }
+
+            if let Some(entry) = self.ngrams.get(&history[start..]) {
+                backoff += entry.backoff;
+            }
+        }
+
+        backoff + self.unknown_log_prob
+    }
+
+    /// The cost of `candidate` standing in for the current word of `context`:
+    /// the negative natural log probability of every n-gram it takes part in
+    /// among the words around it.
+    pub fn context_cost(&self, context: &WordContext, candidate: &str) -> Weight {
+        let before = match (&context.second_before, &context.first_before) {
+            (Some((_, second)), Some((_, first))) => {
+                vec![self.word_id(second), self.word_id(first)]
+            }
+            (None, Some((_, first))) => vec![self.word_id(SENTENCE_START), self.word_id(first)],
+            _ => vec![self.word_id(SENTENCE_START)],
+        };
+        let after = context
+            .first_after
+            .iter()
+            .chain(context.second_after.iter())
+            .map(|(_, word)| self.word_id(word));
+
+        let mut words = before;
+        let first = words.len();
+        words.push(self.word_id(candidate));
+        words.extend(after);
+
+        let log_prob = (first..words.len())
+            .take(self.order.max(1))
+            .map(|i| self.log_prob(&words[..i], words[i]))
+            .sum::<f32>();
+
+        -log_prob * std::f32::consts::LN_10
+    }
+}
+
+fn invalid_line(index: usize, line: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid ARPA line {}: {:?}", index + 1, line),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARPA: &str = "\\data\\
+ngram 1=5
+ngram 2=3
+
+\\1-grams:
+-1.0\t<s>\t-0.5
+-1.0\tthere\t-0.25
+-1.5\ttheir\t-0.25
+-1.0\tis\t-0.5
+-2.0\thouse
+
+\\2-grams:
+-0.25\tthere is
+-0.5\ttheir house
+-0.75\t<s> their
+
+\\end\\
+";
+
+    fn context(before: &[&str], after: &[&str]) -> WordContext {
+        let word = |words: &[&str], i: usize| words.get(i).map(|w| (0, w.to_string()));
+
+        WordContext {
+            current: (0, String::new()),
+            first_before: word(before, 0),
+            second_before: word(before, 1),
+            first_after: word(after, 0),
+            second_after: word(after, 1),
+        }
+    }
+
+    #[test]
+    fn backoff() {
+        let model = NgramModel::from_arpa(ARPA.as_bytes()).unwrap();
+        let id = |w| model.word_id(w);
+
+        assert_eq!(model.order(), 2);
+        assert_eq!(model.log_prob(&[id("there")], id("is")), -0.25);
+        assert_eq!(model.log_prob(&[id("is")], id("there")), -1.5);
+        assert_eq!(model.log_prob(&[None], id("there")), -1.0);
+        assert_eq!(model.log_prob(&[], None), -2.0);
+        assert_eq!(id("There"), id("there"));
+    }
+
+    #[test]
+    fn context_picks_neighbours() {
+        let model = NgramModel::from_arpa(ARPA.as_bytes()).unwrap();
+
+        let is = context(&[], &["is"]);
+        assert!(model.context_cost(&is, "there") < model.context_cost(&is, "their"));
+
+        let house = context(&[], &["house"]);
+        assert!(model.context_cost(&house, "their") < model.context_cost(&house, "there"));
+    }
+}
@@ -0,0 +1,548 @@
+use std::borrow::Cow;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use smol_str::SmolStr;
+
+use super::suggestion::{CheckResult, SuggestResult, Suggestion};
+use super::{Speller, SpellerConfig};
+use crate::tokenizer::case_handling::{
+    upper_case, upper_first, word_variants, CaseHandler, CaseMutation,
+};
+use crate::tokenizer::WordContext;
+use crate::types::{NormalizationForm, Weight};
+
+#[derive(Debug, thiserror::Error)]
+pub enum PersonalDictionaryError {
+    #[error("IO error")]
+    Io(#[from] io::Error),
+
+    #[error("Invalid weight on line {0}: {1:?}")]
+    InvalidWeight(usize, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Entry {
+    /// Accepted and suggested, at the given weight.
+    Word(Weight),
+    /// Accepted, but never suggested.
+    Ignored,
+}
+
+/// Wraps a speller with a list of words added by the user at runtime.
+///
+/// Added words are accepted by `is_correct` and suggested for misspellings
+/// within `max_distance` edits of them, weighted by the word's own weight
+/// plus `edit_weight` per edit. Ignored words are accepted but never
+/// suggested. Case variants are handled like in the lexicon, so an added
+/// "word" also accepts "Word". Words are kept in NFC and matched whichever
+/// normalization form they are typed in.
+///
+/// The dictionary is saved as plain text, one word per line. A word may be
+/// followed by a tab and its weight, and ignored words start with `!`.
+/// Blank lines and lines starting with `#` are skipped. Within a word, `\\`,
+/// `\t`, `\n` and `\r` stand for a backslash, tab, newline and carriage
+/// return, and a backslash before a leading `#` or `!` makes it part of the
+/// word.
+#[derive(Debug)]
+pub struct PersonalDictionary<S: ?Sized> {
+    speller: Arc<S>,
+    entries: RwLock<HashMap<SmolStr, Entry>>,
+    max_distance: usize,
+    edit_weight: Weight,
+}
+
+impl<S: Speller + ?Sized> PersonalDictionary<S> {
+    /// An empty dictionary suggesting its words up to two edits away, at a
+    /// weight of 5 per edit.
+    pub fn new(speller: Arc<S>) -> Arc<PersonalDictionary<S>> {
+        PersonalDictionary::with_edit_weight(speller, 2, 5.0)
+    }
+
+    pub fn with_edit_weight(
+        speller: Arc<S>,
+        max_distance: usize,
+        edit_weight: Weight,
+    ) -> Arc<PersonalDictionary<S>> {
+        Arc::new(PersonalDictionary {
+            speller,
+            entries: RwLock::new(HashMap::new()),
+            max_distance,
+            edit_weight,
+        })
+    }
+
+    pub fn speller(&self) -> &Arc<S> {
+        &self.speller
+    }
+
+    pub fn add_word(&self, word: &str) {
+        self.add_word_with_weight(word, 0.0);
+    }
+
+    /// Adds a word with a weight, like a lexicon entry's: lighter words are
+    /// suggested first.
+    pub fn add_word_with_weight(&self, word: &str, weight: Weight) {
+        self.entries
+            .write()
+            .insert(nfc(word).into(), Entry::Word(weight));
+    }
+
+    /// Accepts the word without ever suggesting it.
+    pub fn ignore_word(&self, word: &str) {
+        self.entries
+            .write()
+            .insert(nfc(word).into(), Entry::Ignored);
+    }
+
+    pub fn remove_word(&self, word: &str) -> bool {
+        self.entries.write().remove(&*nfc(word)).is_some()
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        let entries = self.entries.read();
+        word_variants(word)
+            .words
+            .iter()
+            .any(|variant| entries.contains_key(&*nfc(variant)))
+    }
+
+    /// Adds every entry of a dictionary file, replacing any entries already
+    /// present for the same words.
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<(), PersonalDictionaryError> {
+        let text = fs::read_to_string(path)?;
+        let parsed = parse_entries(&text)?;
+
+        self.entries.write().extend(
+            parsed
+                .into_iter()
+                .map(|(word, entry)| (SmolStr::from(nfc(&word)), entry)),
+        );
+        Ok(())
+    }
+
+    /// Writes the dictionary out, sorted, in the format `load` reads.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersonalDictionaryError> {
+        let mut entries = self
+            .entries
+            .read()
+            .iter()
+            .map(|(word, entry)| (word.clone(), *entry))
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        write_entries(&mut file, &entries)?;
+
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Added words close enough to `word`, or one of its case variants, to be
+    /// suggested for it.
+    fn candidates(&self, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let max_weight = config.max_weight.unwrap_or(f32::MAX);
+        let form = NormalizationForm::of(word);
+        let variants = word_variants(&nfc(word)).words;
+
+        self.entries
+            .read()
+            .iter()
+            .filter_map(|(value, entry)| {
+                let weight = match entry {
+                    Entry::Word(weight) => *weight,
+                    Entry::Ignored => return None,
+                };
+
+                let distance = variants
+                    .iter()
+                    .map(|variant| strsim::damerau_levenshtein(variant, value))
+                    .min()
+                    .unwrap_or(usize::MAX);
+                if distance > self.max_distance {
+                    return None;
+                }
+
+                let weight = weight + self.edit_weight * distance as Weight;
+                if weight > max_weight {
+                    return None;
+                }
+
+                Some(Suggestion::new(in_form(value, form), weight))
+            })
+            .collect()
+    }
+
+    /// Merges the added words near `word` into the wrapped speller's
+    /// suggestions, keeping the lighter weight of any duplicates.
+    fn merge(&self, word: &str, config: &SpellerConfig, suggestions: &mut Vec<Suggestion>) {
+        for candidate in self.candidates(word, config) {
+            match suggestions.iter_mut().find(|x| x.value == candidate.value) {
+                Some(existing) => {
+                    if existing.weight > candidate.weight {
+                        existing.weight = candidate.weight;
+                    }
+                }
+                None => suggestions.push(candidate),
+            }
+        }
+
+        suggestions.sort();
+
+        if let Some(n_best) = config.n_best {
+            suggestions.truncate(n_best);
+        }
+    }
+}
+
+fn parse_entries(text: &str) -> Result<Vec<(SmolStr, Entry)>, PersonalDictionaryError> {
+    let mut parsed = vec![];
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(word) = line.strip_prefix('!') {
+            parsed.push((unescape(word), Entry::Ignored));
+            continue;
+        }
+
+        let mut parts = line.splitn(2, '\t');
+        let word = parts.next().unwrap_or("");
+        let weight = match parts.next() {
+            Some(value) => value
+                .trim()
+                .parse::<Weight>()
+                .map_err(|_| PersonalDictionaryError::InvalidWeight(i + 1, value.to_string()))?,
+            None => 0.0,
+        };
+
+        parsed.push((unescape(word), Entry::Word(weight)));
+    }
+
+    Ok(parsed)
+}
+
+fn write_entries<W: Write>(mut writer: W, entries: &[(SmolStr, Entry)]) -> io::Result<()> {
+    for (word, entry) in entries {
+        let word = escape(word);
+
+        match entry {
+            Entry::Word(weight) => {
+                if *weight == 0.0 {
+                    writeln!(writer, "{}", word)?
+                } else {
+                    writeln!(writer, "{}\t{}", word, weight)?
+                }
+            }
+            Entry::Ignored => writeln!(writer, "!{}", word)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// The form words are kept in.
+fn nfc(word: &str) -> Cow<'_, str> {
+    NormalizationForm::Nfc.normalize(word)
+}
+
+/// An added word in `form`, the one the word it is suggested for was typed
+/// in.
+fn in_form(value: &str, form: Option<NormalizationForm>) -> SmolStr {
+    match form {
+        Some(form) => SmolStr::from(form.normalize(value)),
+        None => SmolStr::from(value),
+    }
+}
+
+/// Escapes a word so it reads back as one word, rather than as a comment,
+/// an ignored word or a word and weight.
+fn escape(word: &str) -> String {
+    let mut escaped = String::with_capacity(word.len());
+
+    if word.starts_with('#') || word.starts_with('!') {
+        escaped.push('\\');
+    }
+
+    for c in word.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+fn unescape(word: &str) -> SmolStr {
+    let mut unescaped = String::with_capacity(word.len());
+    let mut chars = word.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+
+    SmolStr::from(unescaped)
+}
+
+impl<S> Speller for PersonalDictionary<S>
+where
+    S: Speller + Send + Sync + ?Sized,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_correct_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> bool {
+        self.contains(word) || self.speller.clone().is_correct_with_config(word, config)
+    }
+
+    /// Whichever of the dictionary and the wrapped speller accepts the
+    /// earlier case variant wins, the dictionary on a tie.
+    fn check_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> CheckResult {
+        let result = self.speller.clone().check_with_config(word, config);
+
+        if let CheckResult::Trivial(_) = result {
+            return result;
+        }
+
+        let variants = word_variants(word).words;
+        let entries = self.entries.read();
+
+        let added = match variants.iter().position(|x| entries.contains_key(&*nfc(x))) {
+            Some(index) => index,
+            None => return result,
+        };
+
+        if let CheckResult::Accepted { variant, .. } = &result {
+            if variants.iter().position(|x| x == variant) < Some(added) {
+                return result;
+            }
+        }
+
+        let weight = match entries[&*nfc(&variants[added])] {
+            Entry::Word(weight) => weight,
+            Entry::Ignored => 0.0,
+        };
+
+        CheckResult::Accepted {
+            variant: variants[added].clone(),
+            exact: variants[added] == word,
+            weight,
+        }
+    }
+
+    fn suggest_with_config(self: Arc<Self>, word: &str, config: &SpellerConfig) -> Vec<Suggestion> {
+        let mut suggestions = self.speller.clone().suggest_with_config(word, config);
+        self.merge(word, config, &mut suggestions);
+        suggestions
+    }
+
+    fn suggest_detailed(self: Arc<Self>, word: &str, config: &SpellerConfig) -> SuggestResult {
+        let mut result = self.speller.clone().suggest_detailed(word, config);
+        self.merge(word, config, &mut result.suggestions);
+        result
+    }
+
+    /// Streams the wrapped speller's suggestions only; added words are not
+    /// part of the search.
+    fn suggest_iter<'a>(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Box<dyn Iterator<Item = Suggestion> + 'a>
+    where
+        Self: 'a,
+    {
+        self.speller.clone().suggest_iter(word, config)
+    }
+
+    fn analyze(self: Arc<Self>, word: &str) -> Vec<Suggestion> {
+        self.speller.clone().analyze(word)
+    }
+
+    fn analyze_suggestions_with_config(
+        self: Arc<Self>,
+        word: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let mut suggestions = self
+            .speller
+            .clone()
+            .analyze_suggestions_with_config(word, config);
+        self.merge(word, config, &mut suggestions);
+        suggestions
+    }
+
+    /// Added words are merged in after the wrapped speller has ranked its
+    /// own suggestions in context.
+    fn suggest_in_context(
+        self: Arc<Self>,
+        context: &WordContext,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let mut suggestions = self.speller.clone().suggest_in_context(context, config);
+        self.merge(&context.current.1, config, &mut suggestions);
+        suggestions
+    }
+
+    /// Added words starting with the prefix are completed at their own
+    /// weight, along with the wrapped speller's completions.
+    fn complete_with_config(
+        self: Arc<Self>,
+        prefix: &str,
+        n: usize,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let mut completions = self.speller.clone().complete_with_config(prefix, n, config);
+        let form = NormalizationForm::of(prefix);
+        let normalized = nfc(prefix);
+        let CaseHandler {
+            mutation, words, ..
+        } = word_variants(&normalized);
+
+        for (value, entry) in self.entries.read().iter() {
+            let weight = match entry {
+                Entry::Word(weight) => *weight,
+                Entry::Ignored => continue,
+            };
+
+            let variant = match words.iter().find(|x| value.starts_with(x.as_str())) {
+                Some(variant) => variant,
+                None => continue,
+            };
+
+            // Words matching a lowered prefix take the prefix's case.
+            let value = if variant.as_str() == normalized {
+                in_form(value, form)
+            } else {
+                match mutation {
+                    CaseMutation::FirstCaps => in_form(&upper_first(value), form),
+                    CaseMutation::AllCaps => in_form(&upper_case(value), form),
+                    CaseMutation::None => in_form(value, form),
+                }
+            };
+
+            match completions.iter_mut().find(|x| x.value == value) {
+                Some(existing) => {
+                    if existing.weight > weight {
+                        existing.weight = weight;
+                    }
+                }
+                None => completions.push(Suggestion::new(value, weight)),
+            }
+        }
+
+        completions.sort();
+        completions.truncate(n);
+        completions
+    }
+
+    /// Added words are accepted as the joined word too.
+    fn suggest_joined(
+        self: Arc<Self>,
+        first: &str,
+        second: &str,
+        config: &SpellerConfig,
+    ) -> Vec<Suggestion> {
+        let mut suggestions = self.speller.clone().suggest_joined(first, second, config);
+        let joined = format!("{}{}", first, second);
+
+        if suggestions.is_empty()
+            && !first.is_empty()
+            && !second.is_empty()
+            && self.contains(&joined)
+        {
+            suggestions.push(Suggestion::new(joined.into(), config.join_penalty));
+        }
+
+        suggestions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::speller::HfstSpeller;
+    use crate::transducer::edit_distance::EditDistanceConfig;
+    use crate::transducer::memory::MemoryTransducer;
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let entries = vec![
+            (SmolStr::from("word"), Entry::Word(0.0)),
+            (SmolStr::from("weighted"), Entry::Word(2.5)),
+            (SmolStr::from("ignored"), Entry::Ignored),
+            (SmolStr::from("#hashtag"), Entry::Word(0.0)),
+            (SmolStr::from("!bang"), Entry::Word(1.0)),
+            (SmolStr::from("!ignored bang"), Entry::Ignored),
+            (SmolStr::from("tab\tbed"), Entry::Word(3.0)),
+            (SmolStr::from("line\nbreak\r"), Entry::Word(0.0)),
+            (SmolStr::from("back\\slash\\t"), Entry::Ignored),
+        ];
+
+        let mut text = vec![];
+        write_entries(&mut text, &entries).unwrap();
+        let text = String::from_utf8(text).unwrap();
+
+        assert_eq!(text.lines().count(), entries.len());
+        assert_eq!(parse_entries(&text).unwrap(), entries);
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let parsed = parse_entries("# comment\n\nword\t1\n!ignored\n").unwrap();
+
+        assert_eq!(
+            parsed,
+            vec![
+                (SmolStr::from("word"), Entry::Word(1.0)),
+                (SmolStr::from("ignored"), Entry::Ignored),
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_either_normalization_form() {
+        let lexicon = MemoryTransducer::acceptor(&[("cat", 0.0)]);
+        let speller: Arc<HfstSpeller<std::fs::File, _, _>> =
+            HfstSpeller::with_edit_distance(lexicon, EditDistanceConfig::default());
+        let dictionary = PersonalDictionary::new(speller);
+
+        let nfc = "caf\u{e9}";
+        let nfd = "cafe\u{301}";
+        dictionary.add_word(nfd);
+
+        assert!(dictionary.contains(nfc));
+        assert!(dictionary.clone().is_correct(nfc));
+        assert!(dictionary.clone().is_correct(nfd));
+
+        // Suggested in the form the misspelling was typed in.
+        let suggestions = dictionary.clone().suggest(&format!("{}s", nfd));
+        assert!(suggestions.iter().any(|x| x.value == nfd));
+        let suggestions = dictionary.clone().suggest(&format!("{}s", nfc));
+        assert!(suggestions.iter().any(|x| x.value == nfc));
+
+        assert!(dictionary.remove_word(nfc));
+        assert!(!dictionary.contains(nfd));
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+use super::suggestion::Suggestion;
+
+/// Post-processes a word's suggestions once the search is done, before they
+/// are sorted by weight and cut down to `n_best`. The search keeps several
+/// times `n_best` suggestions when there are rankers, so a ranker can bring
+/// up ones that would otherwise have been cut.
+///
+/// A ranker may change weights, drop suggestions or add new ones, which is
+/// enough for house rules such as keyboard proximity, frequency lists or
+/// blocklists. Rankers are registered on the speller with
+/// `HfstSpeller::add_ranker`, or passed for a single lookup in
+/// `SpellerConfig::rankers`, and run in that order.
+///
+/// Any `Fn(&str, &mut Vec<Suggestion>)` closure is a ranker.
+pub trait SuggestionRanker: Send + Sync {
+    /// Reworks the suggestions for `word`, the word as it was typed, or the
+    /// completions of it when it is a prefix being completed. The list is in
+    /// no particular order.
+    fn rank(&self, word: &str, suggestions: &mut Vec<Suggestion>);
+}
+
+impl<F> SuggestionRanker for F
+where
+    F: Fn(&str, &mut Vec<Suggestion>) + Send + Sync,
+{
+    fn rank(&self, word: &str, suggestions: &mut Vec<Suggestion>) {
+        self(word, suggestions)
+    }
+}
+
+impl fmt::Debug for dyn SuggestionRanker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SuggestionRanker")
+    }
+}
@@ -8,11 +8,20 @@ use std::cmp::Ordering::Equal;
 pub struct Suggestion {
     pub value: SmolStr,
     pub weight: Weight,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub analyses: Vec<SmolStr>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alignment: Vec<Edit>,
 }
 
 impl Suggestion {
     pub fn new(value: SmolStr, weight: Weight) -> Suggestion {
-        Suggestion { value, weight }
+        Suggestion {
+            value,
+            weight,
+            analyses: vec![],
+            alignment: vec![],
+        }
     }
 
     pub fn value(&self) -> &str {
@@ -22,6 +31,35 @@ impl Suggestion {
     pub fn weight(&self) -> Weight {
         self.weight
     }
+
+    /// Lexicon output strings (lemma and tags) of the paths that produced
+    /// this suggestion, best first. Only filled in by `analyze_suggestions`.
+    pub fn analyses(&self) -> &[SmolStr] {
+        &self.analyses
+    }
+
+    /// Error model arcs taken on the best path to this suggestion, in input
+    /// order. Only filled in when `SpellerConfig::alignment` is set. With case
+    /// handling, the input side is the case variant that was searched.
+    pub fn alignment(&self) -> &[Edit] {
+        &self.alignment
+    }
+}
+
+/// One error model arc on the path from the input to a suggestion. An empty
+/// `input` is an insertion and an empty `output` a deletion; arcs where both
+/// sides are equal copy the input unchanged.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edit {
+    pub input: SmolStr,
+    pub output: SmolStr,
+    pub weight: Weight,
+}
+
+impl Edit {
+    pub fn is_identity(&self) -> bool {
+        self.input == self.output
+    }
 }
 
 impl PartialOrd for Suggestion {
@@ -49,3 +87,82 @@ impl PartialEq for Suggestion {
 }
 
 impl Eq for Suggestion {}
+
+/// Why a suggestion search stopped before exhausting its search space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Truncation {
+    IterationLimit,
+    TimeLimit,
+    Cancelled,
+}
+
+/// Counters describing how a suggestion search went, for tuning `beam`,
+/// `max_weight` and `node_pool_size`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SearchStats {
+    /// Number of search nodes expanded.
+    pub iterations: usize,
+    /// Largest number of nodes waiting in the queue at any one time.
+    pub max_queue_len: usize,
+    /// Number of nodes dropped for duplicating a lighter node already seen.
+    pub pruned: usize,
+    /// Set if the search was cut short rather than run to completion.
+    pub truncated: Option<Truncation>,
+    /// The weight limit in effect when the search ended.
+    pub weight_limit: Weight,
+}
+
+impl SearchStats {
+    /// Folds the statistics of another search, such as one for a different
+    /// case variant of the same word, into these.
+    pub fn merge(&mut self, other: &SearchStats) {
+        self.iterations += other.iterations;
+        self.pruned += other.pruned;
+        self.max_queue_len = self.max_queue_len.max(other.max_queue_len);
+        self.truncated = self.truncated.or(other.truncated);
+        self.weight_limit = self.weight_limit.max(other.weight_limit);
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SuggestResult {
+    pub suggestions: Vec<Suggestion>,
+    pub stats: SearchStats,
+}
+
+/// How `Speller::check` judged a word.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CheckResult {
+    /// The lexicon accepts `variant`, the first of the word's case variants
+    /// that it accepts. `exact` is set if that is the word as typed rather
+    /// than a recased form, and `weight` is the weight of its lightest path.
+    Accepted {
+        variant: SmolStr,
+        exact: bool,
+        weight: Weight,
+    },
+    /// Accepted without a lookup, as there is nothing to check.
+    Trivial(TrivialReason),
+    /// Neither the word nor any of its case variants is accepted.
+    Rejected,
+}
+
+/// Why a word was accepted without a lookup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrivialReason {
+    Empty,
+    /// Only digits, punctuation and the like.
+    NoLetters,
+}
+
+impl CheckResult {
+    pub fn is_accepted(&self) -> bool {
+        !matches!(self, CheckResult::Rejected)
+    }
+
+    /// Whether the word was accepted only in another case, such as "paris"
+    /// for "Paris", which usually means a capitalization error.
+    pub fn is_recased(&self) -> bool {
+        matches!(self, CheckResult::Accepted { exact: false, .. })
+    }
+}
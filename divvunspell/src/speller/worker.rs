@@ -1,33 +1,130 @@
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use smol_str::SmolStr;
+use std::cmp::Ordering::Equal;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::f32;
 use std::sync::Arc;
-
-use lifeguard::{Pool, Recycled};
+use std::time::Instant;
 
 use super::{HfstSpeller, SpellerConfig};
-use crate::speller::suggestion::Suggestion;
-use crate::transducer::tree_node::TreeNode;
-use crate::transducer::Transducer;
+use crate::speller::suggestion::{Edit, SearchStats, SuggestResult, Suggestion, Truncation};
+use crate::transducer::tree_node::{EditArc, NodeArena, TreeNode, TreeNodeKey};
+use crate::transducer::{Transducer, TransducerAlphabet};
 use crate::types::{SymbolNumber, Weight};
 
 #[inline(always)]
-fn speller_start_node(pool: &Pool<TreeNode>, size: usize) -> Vec<Recycled<TreeNode>> {
-    let start_node = TreeNode::empty(pool, vec![0; size]);
-    let mut nodes = Vec::with_capacity(256);
-    nodes.push(start_node);
-    nodes
+fn speller_max_weight(config: &SpellerConfig) -> Weight {
+    config.max_weight.unwrap_or(f32::MAX)
 }
 
 #[inline(always)]
-fn speller_max_weight(config: &SpellerConfig) -> Weight {
-    config.max_weight.unwrap_or(f32::MAX)
+fn tag_matches(pattern: &str, tag: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => tag.starts_with(prefix),
+        None => tag == pattern,
+    }
+}
+
+/// Collects the lexicon symbols for every tag matched by the given patterns.
+fn tag_symbols<'a>(
+    alphabet: &TransducerAlphabet,
+    patterns: impl Iterator<Item = &'a SmolStr> + Clone,
+) -> Vec<SymbolNumber> {
+    alphabet
+        .key_table()
+        .iter()
+        .enumerate()
+        .filter(|(i, key)| {
+            alphabet.is_tag(*i as SymbolNumber)
+                && patterns.clone().any(|pattern| tag_matches(pattern, key))
+        })
+        .map(|(i, _)| i as SymbolNumber)
+        .collect()
 }
 
+/// What a best-first search makes of its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchMode {
+    /// Corrections of the whole input.
+    Suggest,
+    /// Lexicon words starting with the input, as it is.
+    Complete,
+    /// Lexicon words starting with a correction of the input.
+    FuzzyComplete,
+}
+
+/// The allocations a lookup needs. The speller keeps a few of these between
+/// lookups, so a search can start without allocating.
+#[derive(Debug)]
+pub(crate) struct SearchBuffers {
+    arena: NodeArena,
+    /// The stack of a depth-first lookup, or the buffer a best-first search
+    /// expands nodes into.
+    nodes: Vec<TreeNode>,
+    queue: Vec<Reverse<TreeNode>>,
+    seen: HashMap<TreeNodeKey, Weight>,
+}
+
+impl SearchBuffers {
+    fn new(capacity: usize, flag_state_size: usize, mutator_flag_state_size: usize) -> Self {
+        SearchBuffers {
+            arena: NodeArena::new(capacity, flag_state_size, mutator_flag_state_size),
+            nodes: Vec::with_capacity(256),
+            queue: Vec::with_capacity(256),
+            seen: HashMap::new(),
+        }
+    }
+
+    fn reset(&mut self, flag_state_size: usize, mutator_flag_state_size: usize) {
+        self.arena.reset(flag_state_size, mutator_flag_state_size);
+        self.nodes.clear();
+        self.queue.clear();
+        self.seen.clear();
+    }
+
+    /// Gives back the memory of an unusually large search, so a pooled set
+    /// does not hold on to its peak size for good. Buffers up to
+    /// `POOLED_GROWTH` times `capacity` are kept as they are, as most lookups
+    /// need about as much as the last one did.
+    fn shrink(&mut self, capacity: usize) {
+        let limit = capacity.max(256) * POOLED_GROWTH;
+
+        if self.arena.capacity() > limit {
+            self.arena.shrink_to(capacity);
+        }
+
+        if self.seen.capacity() > limit {
+            self.seen.clear();
+            self.seen.shrink_to(capacity);
+        }
+
+        if self.nodes.capacity() > limit {
+            self.nodes.clear();
+            self.nodes.shrink_to(256);
+        }
+
+        if self.queue.capacity() > limit {
+            self.queue.clear();
+            self.queue.shrink_to(256);
+        }
+    }
+}
+
+/// How far past `node_pool_size` a pooled set of search buffers may grow
+/// before it is shrunk on its way back to the pool.
+const POOLED_GROWTH: usize = 64;
+
 pub struct SpellerWorker<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
     speller: Arc<HfstSpeller<F, T, U>>,
+    /// Index of the error model in the speller's cascade.
+    error_model: usize,
     input: Vec<SymbolNumber>,
     config: SpellerConfig,
+    /// Blocked tag symbols, per lexicon.
+    no_accept_symbols: Vec<Vec<SymbolNumber>>,
+    no_suggest_symbols: Vec<Vec<SymbolNumber>>,
+    deadline: Option<Instant>,
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -40,25 +137,90 @@ where
     #[inline(always)]
     pub(crate) fn new(
         speller: Arc<HfstSpeller<F, T, U>>,
+        error_model: usize,
         input: Vec<SymbolNumber>,
         config: SpellerConfig,
     ) -> SpellerWorker<F, T, U> {
+        let no_accept_symbols = speller
+            .lexicons()
+            .map(|lexicon| tag_symbols(lexicon.alphabet(), config.no_accept_tags.iter()))
+            .collect();
+        let no_suggest_symbols = speller
+            .lexicons()
+            .map(|lexicon| {
+                tag_symbols(
+                    lexicon.alphabet(),
+                    config
+                        .no_suggest_tags
+                        .iter()
+                        .chain(config.no_accept_tags.iter()),
+                )
+            })
+            .collect();
+
+        let deadline = config.time_limit.map(|limit| Instant::now() + limit);
+
         SpellerWorker {
             speller,
+            error_model,
             input,
             config,
+            no_accept_symbols,
+            no_suggest_symbols,
+            deadline,
         }
     }
 
     #[inline(always)]
-    fn lexicon_epsilons<'a>(
+    fn mutator(&self) -> &T {
+        self.speller.mutator_at(self.error_model)
+    }
+
+    /// The weight limit for suggestions, the tighter of the configured one
+    /// and the error model's own.
+    fn suggestion_max_weight(&self) -> Weight {
+        let max_weight = speller_max_weight(&self.config);
+
+        match self.speller.error_model_max_weight(self.error_model) {
+            Some(limit) => max_weight.min(limit),
+            None => max_weight,
+        }
+    }
+
+    /// The lexicon the given node's path runs through.
+    #[inline(always)]
+    fn lexicon(&self, node: &TreeNode) -> &U {
+        self.speller.lexicon_at(node.lexicon_index as usize)
+    }
+
+    #[inline(always)]
+    fn alphabet_translator(&self, node: &TreeNode) -> &[SymbolNumber] {
+        self.speller
+            .alphabet_translator(self.error_model, node.lexicon_index as usize)
+    }
+
+    /// One start node per lexicon, weighted by the lexicon's weight offset.
+    fn start_nodes<'a>(&'a self, arena: &'a NodeArena) -> impl Iterator<Item = TreeNode> + 'a {
+        self.speller
+            .lexicon_weights()
+            .enumerate()
+            .map(move |(i, weight)| {
+                let mut node = TreeNode::empty(arena);
+                node.lexicon_index = i as u32;
+                node.weight = weight;
+                node
+            })
+    }
+
+    #[inline(always)]
+    fn lexicon_epsilons(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let lexicon = self.speller.lexicon();
+        let lexicon = self.lexicon(next_node);
         let operations = lexicon.alphabet().operations();
 
         if !lexicon.has_epsilons_or_flags(next_node.lexicon_state + 1) {
@@ -75,7 +237,7 @@ where
                     if self
                         .is_under_weight_limit(max_weight, next_node.weight() + transition_weight)
                     {
-                        let new_node = next_node.update_lexicon(pool, transition);
+                        let new_node = next_node.update_lexicon(arena, transition);
                         output_nodes.push(new_node);
                     }
                 } else {
@@ -87,7 +249,8 @@ where
                             continue;
                         }
 
-                        if let Some(applied_node) = next_node.apply_operation(pool, op, &transition)
+                        if let Some(applied_node) =
+                            next_node.apply_operation(arena, op, &transition)
                         {
                             output_nodes.push(applied_node);
                         }
@@ -100,30 +263,52 @@ where
     }
 
     #[inline(always)]
-    fn mutator_epsilons<'a>(
+    fn mutator_epsilons(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let mutator = self.speller.mutator();
-        let lexicon = self.speller.lexicon();
-        let alphabet_translator = self.speller.alphabet_translator();
+        let mutator = self.mutator();
+        let lexicon = self.lexicon(next_node);
+        let alphabet_translator = self.alphabet_translator(next_node);
+        let operations = mutator.alphabet().operations();
 
-        if !mutator.has_transitions(next_node.mutator_state + 1, Some(0)) {
+        if !mutator.has_epsilons_or_flags(next_node.mutator_state + 1) {
             return;
         }
 
         let mut next_m = mutator.next(next_node.mutator_state, 0).unwrap();
 
-        while let Some(transition) = mutator.take_epsilons(next_m) {
+        while let Some(transition) = mutator.take_epsilons_and_flags(next_m) {
+            // Flags in the error model constrain where edits may happen; they
+            // neither consume input nor reach the lexicon.
+            if let Some(op) = mutator
+                .transition_input_symbol(next_m)
+                .and_then(|sym| operations.get(&sym))
+            {
+                if self.is_under_weight_limit(
+                    max_weight,
+                    next_node.weight() + transition.weight().unwrap(),
+                ) {
+                    if let Some(applied_node) =
+                        next_node.apply_mutator_operation(arena, op, &transition)
+                    {
+                        output_nodes.push(applied_node);
+                    }
+                }
+
+                next_m += 1;
+                continue;
+            }
+
             if let Some(0) = transition.symbol() {
                 if self.is_under_weight_limit(
                     max_weight,
                     next_node.weight() + transition.weight().unwrap(),
                 ) {
-                    let new_node = next_node.update_mutator(pool, transition);
+                    let new_node = next_node.update_mutator(transition);
                     output_nodes.push(new_node);
                 }
 
@@ -144,13 +329,14 @@ where
                             lexicon.alphabet().unknown(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 0,
+                                self.edit(next_node, false, sym, transition.weight().unwrap()),
                                 output_nodes,
                             );
                         }
@@ -160,13 +346,14 @@ where
                             lexicon.alphabet().identity(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 0,
+                                self.edit(next_node, false, sym, transition.weight().unwrap()),
                                 output_nodes,
                             );
                         }
@@ -177,13 +364,14 @@ where
                 }
 
                 self.queue_lexicon_arcs(
-                    pool,
+                    arena,
                     max_weight,
                     &next_node,
                     trans_sym,
                     transition.target().unwrap(),
                     transition.weight().unwrap(),
                     0,
+                    self.edit(next_node, false, sym, transition.weight().unwrap()),
                     output_nodes,
                 );
             }
@@ -193,18 +381,19 @@ where
     }
 
     #[inline(always)]
-    fn queue_lexicon_arcs<'a>(
+    fn queue_lexicon_arcs(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
         input_sym: SymbolNumber,
         mutator_state: u32,
         mutator_weight: Weight,
         input_increment: i16,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        edit: Option<EditArc>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let lexicon = self.speller.lexicon();
+        let lexicon = self.lexicon(next_node);
         let identity = lexicon.alphabet().identity();
         let mut next = lexicon.next(next_node.lexicon_state, input_sym).unwrap();
 
@@ -225,8 +414,8 @@ where
                 );
 
                 if is_under_weight_limit {
-                    let new_node = next_node.update(
-                        pool,
+                    let mut new_node = next_node.update(
+                        arena,
                         sym,
                         Some(next_node.input_state + input_increment as u32),
                         mutator_state,
@@ -234,6 +423,10 @@ where
                         noneps_trans.weight().unwrap() + mutator_weight,
                     );
 
+                    if let Some(edit) = edit {
+                        new_node.edits = arena.push_edit(new_node.edits, edit);
+                    }
+
                     output_nodes.push(new_node);
                 }
             }
@@ -243,17 +436,17 @@ where
     }
 
     #[inline(always)]
-    fn queue_mutator_arcs<'a>(
+    fn queue_mutator_arcs(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
         input_sym: SymbolNumber,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let mutator = self.speller.mutator();
-        let lexicon = self.speller.lexicon();
-        let alphabet_translator = self.speller.alphabet_translator();
+        let mutator = self.mutator();
+        let lexicon = self.lexicon(next_node);
+        let alphabet_translator = self.alphabet_translator(next_node);
 
         let mut next_m = mutator.next(next_node.mutator_state, input_sym).unwrap();
 
@@ -263,8 +456,8 @@ where
             if let Some(0) = symbol {
                 let transition_weight = transition.weight().unwrap();
                 if self.is_under_weight_limit(max_weight, next_node.weight() + transition_weight) {
-                    let new_node = next_node.update(
-                        pool,
+                    let mut new_node = next_node.update(
+                        arena,
                         0,
                         Some(next_node.input_state + 1),
                         transition.target().unwrap(),
@@ -272,6 +465,10 @@ where
                         transition_weight,
                     );
 
+                    if let Some(edit) = self.edit(next_node, true, 0, transition_weight) {
+                        new_node.edits = arena.push_edit(new_node.edits, edit);
+                    }
+
                     output_nodes.push(new_node);
                 }
 
@@ -289,13 +486,14 @@ where
                             lexicon.alphabet().unknown(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().unknown().unwrap(),
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 1,
+                                self.edit(next_node, true, sym, transition.weight().unwrap()),
                                 output_nodes,
                             );
                         }
@@ -304,13 +502,14 @@ where
                             lexicon.alphabet().identity(),
                         ) {
                             self.queue_lexicon_arcs(
-                                pool,
+                                arena,
                                 max_weight,
                                 &next_node,
                                 lexicon.alphabet().identity().unwrap(),
                                 transition.target().unwrap(),
                                 transition.weight().unwrap(),
                                 1,
+                                self.edit(next_node, true, sym, transition.weight().unwrap()),
                                 output_nodes,
                             );
                         }
@@ -320,13 +519,14 @@ where
                 }
 
                 self.queue_lexicon_arcs(
-                    pool,
+                    arena,
                     max_weight,
                     &next_node,
                     trans_sym,
                     transition.target().unwrap(),
                     transition.weight().unwrap(),
                     1,
+                    self.edit(next_node, true, sym, transition.weight().unwrap()),
                     output_nodes,
                 );
 
@@ -336,14 +536,14 @@ where
     }
 
     #[inline(always)]
-    fn consume_input<'a>(
+    fn consume_input(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let mutator = self.speller.mutator();
+        let mutator = self.mutator();
         let input_state = next_node.input_state as usize;
 
         if input_state >= self.input.len() {
@@ -359,7 +559,7 @@ where
                     .has_transitions(next_node.mutator_state + 1, mutator.alphabet().identity())
                 {
                     self.queue_mutator_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         mutator.alphabet().identity().unwrap(),
@@ -372,7 +572,7 @@ where
                     .has_transitions(next_node.mutator_state + 1, mutator.alphabet().unknown())
                 {
                     self.queue_mutator_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         mutator.alphabet().unknown().unwrap(),
@@ -381,21 +581,20 @@ where
                 }
             }
         } else {
-            self.queue_mutator_arcs(pool, max_weight, &next_node, input_sym, output_nodes)
+            self.queue_mutator_arcs(arena, max_weight, &next_node, input_sym, output_nodes)
         }
     }
 
     #[inline(always)]
-    fn lexicon_consume<'a>(
+    fn lexicon_consume(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         max_weight: Weight,
         next_node: &TreeNode,
-        output_nodes: &mut Vec<Recycled<'a, TreeNode>>,
+        output_nodes: &mut Vec<TreeNode>,
     ) {
-        let mutator = self.speller.mutator();
-        let lexicon = self.speller.lexicon();
-        let alphabet_translator = self.speller.alphabet_translator();
+        let lexicon = self.lexicon(next_node);
+        let alphabet_translator = self.alphabet_translator(next_node);
         let input_state = next_node.input_state as usize;
 
         if input_state >= self.input.len() {
@@ -408,30 +607,35 @@ where
         if !lexicon.has_transitions(next_lexicon_state, Some(input_sym)) {
             // we have no regular transitions for this
             if input_sym >= lexicon.alphabet().initial_symbol_count() {
-                let identity = mutator.alphabet().identity();
+                // identity and unknown are looked up on the lexicon's own
+                // transitions, so they must be its symbol numbers, not the
+                // error model's
+                let identity = lexicon.alphabet().identity();
                 if lexicon.has_transitions(next_lexicon_state, identity) {
                     self.queue_lexicon_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         identity.unwrap(),
                         next_node.mutator_state,
                         0.0,
                         1,
+                        None,
                         output_nodes,
                     );
                 }
 
-                let unknown = mutator.alphabet().unknown();
+                let unknown = lexicon.alphabet().unknown();
                 if lexicon.has_transitions(next_lexicon_state, unknown) {
                     self.queue_lexicon_arcs(
-                        pool,
+                        arena,
                         max_weight,
                         &next_node,
                         unknown.unwrap(),
                         next_node.mutator_state,
                         0.0,
                         1,
+                        None,
                         output_nodes,
                     );
                 }
@@ -441,25 +645,73 @@ where
         }
 
         self.queue_lexicon_arcs(
-            pool,
+            arena,
             max_weight,
             &next_node,
             input_sym,
             next_node.mutator_state,
             0.0,
             1,
+            None,
             output_nodes,
         );
     }
 
+    /// Follows every lexicon arc out of a node whose input is used up,
+    /// writing whatever the lexicon does, to complete the word.
+    fn lexicon_complete(
+        &self,
+        arena: &mut NodeArena,
+        max_weight: Weight,
+        next_node: &TreeNode,
+        output_nodes: &mut Vec<TreeNode>,
+    ) {
+        let lexicon = self.lexicon(next_node);
+        let alphabet = lexicon.alphabet();
+        let next_lexicon_state = next_node.lexicon_state + 1;
+
+        for sym in 1..alphabet.initial_symbol_count() {
+            // Flags are taken along with the epsilons, and identity and
+            // unknown arcs have nothing to copy from.
+            if alphabet.is_flag(sym)
+                || Some(sym) == alphabet.identity()
+                || Some(sym) == alphabet.unknown()
+                || !lexicon.has_transitions(next_lexicon_state, Some(sym))
+            {
+                continue;
+            }
+
+            let mut next = lexicon.next(next_node.lexicon_state, sym).unwrap();
+
+            while let Some(transition) = lexicon.take_non_epsilons(next, sym) {
+                let weight = transition.weight().unwrap();
+
+                if self.is_under_weight_limit(max_weight, next_node.weight() + weight) {
+                    output_nodes.push(next_node.update(
+                        arena,
+                        transition.symbol().unwrap(),
+                        None,
+                        next_node.mutator_state,
+                        transition.target().unwrap(),
+                        weight,
+                    ));
+                }
+
+                next += 1;
+            }
+        }
+    }
+
+    /// The weight limit for the search so far: the error model's limit, or
+    /// the configured one if tighter, narrowed by the beam around the best
+    /// suggestion and by the n-th best once `n_best` have been found.
     #[inline(always)]
-    fn update_weight_limit(&self, best_weight: Weight, suggestions: &[Suggestion]) -> Weight {
+    fn update_weight_limit(&self, best_weight: Weight, n_best_weight: Option<Weight>) -> Weight {
         use std::cmp::Ordering::{Equal, Less};
 
-        let c = &self.config;
-        let mut max_weight = c.max_weight.unwrap_or(f32::MAX);
+        let mut max_weight = self.suggestion_max_weight();
 
-        if let Some(beam) = c.beam {
+        if let Some(beam) = self.config.beam {
             let candidate_weight = best_weight + beam;
 
             max_weight = match max_weight.partial_cmp(&candidate_weight).unwrap_or(Equal) {
@@ -468,146 +720,615 @@ where
             };
         }
 
-        if c.n_best.is_some() && suggestions.len() >= c.n_best.unwrap() {
-            if let Some(sugg) = suggestions.last() {
-                return sugg.weight();
-            }
+        if let Some(weight) = n_best_weight {
+            return weight.min(max_weight);
         }
 
         max_weight
     }
 
+    /// The alignment record for a mutator arc leaving `node`, if alignments
+    /// were asked for. Identity and unknown outputs copy the input symbol.
+    #[inline(always)]
+    fn edit(
+        &self,
+        node: &TreeNode,
+        consumes_input: bool,
+        output: SymbolNumber,
+        weight: Weight,
+    ) -> Option<EditArc> {
+        if !self.config.alignment {
+            return None;
+        }
+
+        let input = if consumes_input {
+            self.input[node.input_state as usize]
+        } else {
+            0
+        };
+
+        let alphabet = self.mutator().alphabet();
+        let output = if Some(output) == alphabet.identity() || Some(output) == alphabet.unknown() {
+            input
+        } else {
+            output
+        };
+
+        Some(EditArc {
+            input,
+            output,
+            weight,
+        })
+    }
+
     #[inline(always)]
     fn is_under_weight_limit(&self, max_weight: Weight, w: Weight) -> bool {
         w <= max_weight
     }
 
+    /// Reports whether the lookup was cancelled or its time budget ran out.
     #[inline(always)]
-    fn state_size(&self) -> usize {
-        self.speller.lexicon().alphabet().state_size() as usize
+    fn interruption(&self) -> Option<Truncation> {
+        if self.config.is_cancelled() {
+            return Some(Truncation::Cancelled);
+        }
+
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Some(Truncation::TimeLimit),
+            _ => None,
+        }
+    }
+
+    /// Takes a set of search buffers from the speller's pool, or allocates
+    /// new ones if the pool is empty.
+    fn take_buffers(&self) -> SearchBuffers {
+        let flag_state_size = self
+            .speller
+            .lexicons()
+            .map(|lexicon| lexicon.alphabet().state_size() as usize)
+            .max()
+            .unwrap_or(0);
+        let mutator_flag_state_size = self.mutator().alphabet().state_size() as usize;
+
+        match self.speller.search_buffers.lock().pop() {
+            Some(mut buffers) => {
+                buffers.reset(flag_state_size, mutator_flag_state_size);
+                buffers
+            }
+            None => SearchBuffers::new(
+                self.config.node_pool_size,
+                flag_state_size,
+                mutator_flag_state_size,
+            ),
+        }
+    }
+
+    /// Hands search buffers back to the speller's pool, unless it is full.
+    fn return_buffers(&self, mut buffers: SearchBuffers) {
+        let pool_size = self.speller.search_pool_size();
+
+        if self.speller.search_buffers.lock().len() >= pool_size {
+            return;
+        }
+
+        buffers.shrink(self.config.node_pool_size);
+
+        let mut pool = self.speller.search_buffers.lock();
+        if pool.len() < pool_size {
+            pool.push(buffers);
+        }
     }
 
     pub(crate) fn is_correct(&self) -> bool {
         let max_weight = speller_max_weight(&self.config);
-        let pool = Pool::with_size_and_max(0, 0);
-        let mut nodes = speller_start_node(&pool, self.state_size() as usize);
+        let mut buffers = self.take_buffers();
+        let SearchBuffers { arena, nodes, .. } = &mut buffers;
+        nodes.extend(self.start_nodes(arena));
+        let mut is_correct = false;
 
         while let Some(next_node) = nodes.pop() {
             if next_node.input_state as usize == self.input.len()
-                && self.speller.lexicon().is_final(next_node.lexicon_state)
+                && self.lexicon(&next_node).is_final(next_node.lexicon_state)
+                && !arena.has_any_symbol(
+                    next_node.string,
+                    &self.no_accept_symbols[next_node.lexicon_index as usize],
+                )
             {
-                return true;
+                is_correct = true;
+                break;
             }
 
-            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut nodes);
-            self.lexicon_consume(&pool, max_weight, &next_node, &mut nodes);
+            self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+            self.lexicon_consume(arena, max_weight, &next_node, nodes);
         }
 
-        false
+        self.return_buffers(buffers);
+        is_correct
     }
 
-    pub(crate) fn suggest(&self) -> Vec<Suggestion> {
-        log::trace!("Beginning suggest");
-
-        let pool = Pool::with_size_and_max(self.config.node_pool_size, self.config.node_pool_size);
-        let mut nodes = speller_start_node(&pool, self.state_size() as usize);
-        let mut corrections = HashMap::new();
-        let mut suggestions: Vec<Suggestion> = vec![];
-        let mut best_weight = self.config.max_weight.unwrap_or(f32::MAX);
-        let key_table = self.speller.mutator().alphabet().key_table();
-
-        let mut iteration_count = 0usize;
+    /// The weight of the lightest path accepting the input, if any does.
+    pub(crate) fn accepted_weight(&self) -> Option<Weight> {
+        let max_weight = speller_max_weight(&self.config);
+        let mut buffers = self.take_buffers();
+        let SearchBuffers { arena, nodes, .. } = &mut buffers;
+        nodes.extend(self.start_nodes(arena));
+        let mut best: Option<Weight> = None;
 
         while let Some(next_node) = nodes.pop() {
-            iteration_count += 1;
-
-            let max_weight = self.update_weight_limit(best_weight, &suggestions);
-
-            if iteration_count >= 10_000_000 {
-                let name: SmolStr = self
-                    .input
-                    .iter()
-                    .map(|s| &*key_table[*s as usize])
-                    .collect();
-                log::warn!("{}: iteration count at {}", name, iteration_count);
-                log::warn!("Node count: {}", nodes.len());
-                log::warn!("Node weight: {}", next_node.weight());
-                break;
-            }
+            let lexicon = self.lexicon(&next_node);
 
-            if !self.is_under_weight_limit(max_weight, next_node.weight()) {
-                continue;
+            if next_node.input_state as usize == self.input.len()
+                && lexicon.is_final(next_node.lexicon_state)
+                && !arena.has_any_symbol(
+                    next_node.string,
+                    &self.no_accept_symbols[next_node.lexicon_index as usize],
+                )
+            {
+                let weight =
+                    next_node.weight() + lexicon.final_weight(next_node.lexicon_state).unwrap();
+                best = Some(best.map_or(weight, |best| best.min(weight)));
             }
 
-            self.lexicon_epsilons(&pool, max_weight, &next_node, &mut nodes);
-            self.mutator_epsilons(&pool, max_weight, &next_node, &mut nodes);
+            self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+            self.lexicon_consume(arena, max_weight, &next_node, nodes);
+        }
 
-            if next_node.input_state as usize != self.input.len() {
-                self.consume_input(&pool, max_weight, &next_node, &mut nodes);
-                continue;
-            }
+        self.return_buffers(buffers);
+        best
+    }
 
-            if !self.speller.mutator().is_final(next_node.mutator_state)
-                || !self.speller.lexicon().is_final(next_node.lexicon_state)
-            {
-                continue;
-            }
+    pub(crate) fn analyze(&self) -> Vec<Suggestion> {
+        self.analyze_filtered(false)
+    }
 
-            let weight = next_node.weight()
-                + self
-                    .speller
-                    .lexicon()
-                    .final_weight(next_node.lexicon_state)
-                    .unwrap()
-                + self
-                    .speller
-                    .mutator()
-                    .final_weight(next_node.mutator_state)
-                    .unwrap();
-
-            if !self.is_under_weight_limit(max_weight, weight) {
-                continue;
-            }
+    /// Analyses of a suggested word, leaving out any path the search itself
+    /// would not have suggested.
+    pub(crate) fn analyze_suggestion(&self) -> Vec<Suggestion> {
+        self.analyze_filtered(true)
+    }
 
-            let string = self
-                .speller
-                .lexicon()
-                .alphabet()
-                .string_from_symbols(&next_node.string);
+    fn analyze_filtered(&self, is_suggestion: bool) -> Vec<Suggestion> {
+        let max_weight = speller_max_weight(&self.config);
+        let mut buffers = self.take_buffers();
+        let SearchBuffers { arena, nodes, .. } = &mut buffers;
+        nodes.extend(self.start_nodes(arena));
+        let mut analyses = HashMap::new();
 
-            if weight < best_weight {
-                best_weight = weight;
-            }
+        while let Some(next_node) = nodes.pop() {
+            let lexicon = self.lexicon(&next_node);
+            let blocked: &[SymbolNumber] = if is_suggestion {
+                &self.no_suggest_symbols[next_node.lexicon_index as usize]
+            } else {
+                &[]
+            };
 
+            if next_node.input_state as usize == self.input.len()
+                && lexicon.is_final(next_node.lexicon_state)
+                && !arena.has_any_symbol(next_node.string, blocked)
             {
-                let entry = corrections.entry(string).or_insert(weight);
+                let weight =
+                    next_node.weight() + lexicon.final_weight(next_node.lexicon_state).unwrap();
+                let string = lexicon
+                    .alphabet()
+                    .string_from_symbols(&arena.string(next_node.string));
+                let entry = analyses.entry(string).or_insert(weight);
 
                 if *entry > weight {
                     *entry = weight;
                 }
             }
 
-            suggestions = self.generate_sorted_suggestions(&corrections);
+            self.lexicon_epsilons(arena, max_weight, &next_node, nodes);
+            self.lexicon_consume(arena, max_weight, &next_node, nodes);
         }
 
-        suggestions
+        self.return_buffers(buffers);
+
+        let mut out = analyses
+            .into_iter()
+            .map(|(value, weight)| Suggestion::new(value, weight))
+            .collect::<Vec<_>>();
+        out.sort();
+        out
     }
 
-    fn generate_sorted_suggestions(
+    /// The weight of a path ending at the given node, if the lexicon is final
+    /// there, and the error model too if the path went through it.
+    fn final_weight(&self, node: &TreeNode, mode: SearchMode) -> Option<Weight> {
+        let lexicon = self.lexicon(node);
+
+        if !lexicon.is_final(node.lexicon_state) {
+            return None;
+        }
+
+        let weight = node.weight() + lexicon.final_weight(node.lexicon_state).unwrap();
+
+        if mode == SearchMode::Complete {
+            return Some(weight);
+        }
+
+        let mutator = self.mutator();
+
+        if !mutator.is_final(node.mutator_state) {
+            return None;
+        }
+
+        Some(weight + mutator.final_weight(node.mutator_state).unwrap())
+    }
+
+    /// The suggestion a node at the end of the input stands for, if both
+    /// transducers are final there and the path may be suggested.
+    fn finished_suggestion(
         &self,
-        corrections: &HashMap<SmolStr, Weight>,
-    ) -> Vec<Suggestion> {
-        let mut c: Vec<Suggestion> = corrections
-            .into_iter()
-            .map(|x| Suggestion::new(x.0.clone(), *x.1))
-            .collect();
+        arena: &NodeArena,
+        node: &TreeNode,
+        max_weight: Weight,
+        mode: SearchMode,
+    ) -> Option<Suggestion> {
+        let weight = self.final_weight(node, mode)?;
+
+        if !self.is_under_weight_limit(max_weight, weight)
+            || arena.has_any_symbol(
+                node.string,
+                &self.no_suggest_symbols[node.lexicon_index as usize],
+            )
+        {
+            return None;
+        }
+
+        let string = self
+            .lexicon(node)
+            .alphabet()
+            .surface_from_symbols(&arena.string(node.string));
+        let mut suggestion = Suggestion::new(string, weight);
+
+        if self.config.alignment {
+            suggestion.alignment = self.alignment(arena, node);
+        }
+
+        Some(suggestion)
+    }
+
+    fn alignment(&self, arena: &NodeArena, node: &TreeNode) -> Vec<Edit> {
+        let key_table = self.mutator().alphabet().key_table();
+
+        arena
+            .edits(node.edits)
+            .iter()
+            .map(|edit| Edit {
+                input: key_table[edit.input as usize].clone(),
+                output: key_table[edit.output as usize].clone(),
+                weight: edit.weight,
+            })
+            .collect()
+    }
+}
+
+/// Yields the suggestions for a word one at a time, best first, as the search
+/// finds them.
+///
+/// The search is best-first: nodes are expanded lightest first from a priority
+/// queue. A finished path is held back until every node left in the queue is
+/// heavier, which relies on the weights of both transducers being
+/// non-negative. Iteration ends after `n_best` suggestions, once the lightest
+/// node left is over the weight limit, or when the search is cut short.
+///
+/// In the completion modes the input is a prefix, and the search carries on
+/// through the lexicon once it is used up, yielding whole words.
+pub struct SuggestionIter<F: crate::vfs::File, T: Transducer<F>, U: Transducer<F>> {
+    worker: SpellerWorker<F, T, U>,
+    mode: SearchMode,
+    arena: NodeArena,
+    nodes: BinaryHeap<Reverse<TreeNode>>,
+    /// Reused buffer for the nodes each expansion produces.
+    expanded: Vec<TreeNode>,
+    /// Best weight found so far for each surface form, yielded or not.
+    found: HashMap<SmolStr, Weight>,
+    /// Finished suggestions waiting for the rest of the queue to catch up.
+    pending: HashMap<SmolStr, Suggestion>,
+    yielded: HashSet<SmolStr>,
+    /// Lightest weight each node configuration has been queued at.
+    seen: HashMap<TreeNodeKey, Weight>,
+    best_weight: Weight,
+    n_best_weight: Option<Weight>,
+    iteration_count: usize,
+    stats: SearchStats,
+    done: bool,
+}
 
-        c.sort();
+impl<F, T, U> SuggestionIter<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    pub(crate) fn new(worker: SpellerWorker<F, T, U>) -> SuggestionIter<F, T, U> {
+        SuggestionIter::with_mode(worker, SearchMode::Suggest)
+    }
 
-        if let Some(n) = self.config.n_best {
-            c.truncate(n);
+    pub(crate) fn with_mode(
+        worker: SpellerWorker<F, T, U>,
+        mode: SearchMode,
+    ) -> SuggestionIter<F, T, U> {
+        let SearchBuffers {
+            arena,
+            nodes,
+            queue,
+            seen,
+        } = worker.take_buffers();
+        let mut queue = BinaryHeap::from(queue);
+        queue.extend(worker.start_nodes(&arena).map(Reverse));
+        let best_weight = worker.suggestion_max_weight();
+
+        SuggestionIter {
+            worker,
+            mode,
+            arena,
+            nodes: queue,
+            expanded: nodes,
+            found: HashMap::new(),
+            pending: HashMap::new(),
+            yielded: HashSet::new(),
+            seen,
+            best_weight,
+            n_best_weight: None,
+            iteration_count: 0,
+            stats: SearchStats::default(),
+            done: false,
         }
+    }
+
+    /// Collects the remaining suggestions along with the search statistics.
+    pub fn into_result(mut self) -> SuggestResult {
+        let suggestions = self.by_ref().collect();
+
+        SuggestResult {
+            suggestions,
+            stats: std::mem::take(&mut self.stats),
+        }
+    }
+
+    /// The best pending suggestion, if nothing still queued can beat it.
+    fn ready(&self) -> Option<SmolStr> {
+        let best = self.pending.values().min()?;
+
+        // Ties are held back as well, so they still come out in order of value.
+        let frontier = self.nodes.peek().map(|Reverse(node)| node.weight());
+
+        if self.done || frontier.map(|w| w > best.weight).unwrap_or(true) {
+            Some(best.value.clone())
+        } else {
+            None
+        }
+    }
 
-        c
+    /// Queues the given nodes, dropping any that duplicate a node already
+    /// queued at the same or a lower weight.
+    fn enqueue(&mut self, nodes: impl Iterator<Item = TreeNode>) {
+        for node in nodes {
+            let key = node.key();
+
+            match self.seen.get(&key) {
+                Some(w) if *w <= node.weight() => {
+                    self.stats.pruned += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.seen.insert(key, node.weight());
+            self.nodes.push(Reverse(node));
+        }
+    }
+
+    fn expand_next(&mut self) {
+        let node = match self.nodes.pop() {
+            Some(Reverse(node)) => node,
+            None => {
+                self.done = true;
+                return;
+            }
+        };
+
+        // A lighter duplicate was queued after this node was.
+        if self
+            .seen
+            .get(&node.key())
+            .map(|w| *w < node.weight())
+            .unwrap_or(false)
+        {
+            self.stats.pruned += 1;
+            return;
+        }
+
+        self.iteration_count += 1;
+        self.stats.iterations = self.iteration_count;
+        self.stats.max_queue_len = self.stats.max_queue_len.max(self.nodes.len() + 1);
+
+        let worker = &self.worker;
+        let max_weight = worker.update_weight_limit(self.best_weight, self.n_best_weight);
+        self.stats.weight_limit = max_weight;
+
+        if worker
+            .config
+            .max_iterations
+            .map(|max| self.iteration_count >= max)
+            .unwrap_or(false)
+        {
+            let key_table = worker.mutator().alphabet().key_table();
+            let name: SmolStr = worker
+                .input
+                .iter()
+                .map(|s| &*key_table[*s as usize])
+                .collect();
+            log::warn!("{}: iteration count at {}", name, self.iteration_count);
+            log::warn!("Node count: {}", self.nodes.len());
+            log::warn!("Node weight: {}", node.weight());
+            self.stats.truncated = Some(Truncation::IterationLimit);
+            self.done = true;
+            return;
+        }
+
+        if self.iteration_count & 0xff == 0 {
+            if let Some(reason) = worker.interruption() {
+                log::debug!(
+                    "Suggest interrupted after {} iterations",
+                    self.iteration_count
+                );
+                self.stats.truncated = Some(reason);
+                self.done = true;
+                return;
+            }
+        }
+
+        if !worker.is_under_weight_limit(max_weight, node.weight()) {
+            // Everything left in the queue is at least as heavy.
+            self.done = true;
+            return;
+        }
+
+        // Completions go on for as long as the lexicon does, so paths tied
+        // with the n-th best are not worth following.
+        if self.mode != SearchMode::Suggest
+            && self
+                .n_best_weight
+                .map(|w| node.weight() >= w)
+                .unwrap_or(false)
+        {
+            self.done = true;
+            return;
+        }
+
+        let arena = &mut self.arena;
+        let mut output_nodes = std::mem::take(&mut self.expanded);
+        let input_done = node.input_state as usize == worker.input.len();
+
+        worker.lexicon_epsilons(arena, max_weight, &node, &mut output_nodes);
+
+        match self.mode {
+            SearchMode::Suggest => {
+                worker.mutator_epsilons(arena, max_weight, &node, &mut output_nodes)
+            }
+            SearchMode::FuzzyComplete if !input_done => {
+                worker.mutator_epsilons(arena, max_weight, &node, &mut output_nodes)
+            }
+            _ => {}
+        }
+
+        let finished = if !input_done {
+            if self.mode == SearchMode::Complete {
+                worker.lexicon_consume(arena, max_weight, &node, &mut output_nodes);
+            } else {
+                worker.consume_input(arena, max_weight, &node, &mut output_nodes);
+            }
+            None
+        } else {
+            if self.mode != SearchMode::Suggest {
+                worker.lexicon_complete(arena, max_weight, &node, &mut output_nodes);
+            }
+            worker.finished_suggestion(arena, &node, max_weight, self.mode)
+        };
+
+        self.enqueue(output_nodes.drain(..));
+        self.expanded = output_nodes;
+
+        if let Some(suggestion) = finished {
+            self.record(suggestion);
+        }
+    }
+
+    fn record(&mut self, suggestion: Suggestion) {
+        if self.yielded.contains(&suggestion.value) {
+            return;
+        }
+
+        let weight = suggestion.weight;
+
+        if weight < self.best_weight {
+            self.best_weight = weight;
+        }
+
+        let is_better = self
+            .found
+            .get(&suggestion.value)
+            .map(|w| *w > weight)
+            .unwrap_or(true);
+
+        if !is_better {
+            return;
+        }
+
+        self.found.insert(suggestion.value.clone(), weight);
+        self.pending.insert(suggestion.value.clone(), suggestion);
+
+        if let Some(n) = self.worker.config.n_best {
+            if n > 0 && self.found.len() >= n {
+                let mut weights = self.found.values().copied().collect::<Vec<_>>();
+                let (_, nth, _) =
+                    weights.select_nth_unstable_by(n - 1, |a, b| a.partial_cmp(b).unwrap_or(Equal));
+                self.n_best_weight = Some(*nth);
+            }
+        }
+    }
+}
+
+impl<F, T, U> Drop for SuggestionIter<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    fn drop(&mut self) {
+        let buffers = SearchBuffers {
+            arena: std::mem::take(&mut self.arena),
+            nodes: std::mem::take(&mut self.expanded),
+            queue: std::mem::take(&mut self.nodes).into_vec(),
+            seen: std::mem::take(&mut self.seen),
+        };
+
+        self.worker.return_buffers(buffers);
+    }
+}
+
+impl<F, T, U> Iterator for SuggestionIter<F, T, U>
+where
+    F: crate::vfs::File,
+    T: Transducer<F>,
+    U: Transducer<F>,
+{
+    type Item = Suggestion;
+
+    fn next(&mut self) -> Option<Suggestion> {
+        if let Some(n) = self.worker.config.n_best {
+            if self.yielded.len() >= n {
+                return None;
+            }
+        }
+
+        loop {
+            if let Some(value) = self.ready() {
+                let suggestion = self.pending.remove(&value).unwrap();
+                let max_weight = self
+                    .worker
+                    .update_weight_limit(self.best_weight, self.n_best_weight);
+
+                // The limit may have tightened since this path was found.
+                if !self
+                    .worker
+                    .is_under_weight_limit(max_weight, suggestion.weight)
+                {
+                    continue;
+                }
+
+                self.yielded.insert(value);
+                return Some(suggestion);
+            }
+
+            if self.done {
+                return None;
+            }
+
+            self.expand_next();
+        }
     }
 }
@@ -1,9 +1,10 @@
 use hashbrown::HashMap;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
+use unic_ucd_category::GeneralCategory;
 
 use crate::transducer::Transducer;
-use crate::types::{OperationsMap, SymbolNumber};
+use crate::types::{NormalizationForm, OperationsMap, SymbolNumber};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransducerAlphabet {
@@ -23,6 +24,16 @@ impl TransducerAlphabet {
         syms.iter().map(|s| &*self.key_table[*s as usize]).collect()
     }
 
+    /// Like `string_from_symbols`, but skips analysis tags such as `+N` or
+    /// `+Use/SpellNoSugg`, yielding only the surface form of the output tape.
+    #[inline(always)]
+    pub fn surface_from_symbols(&self, syms: &[SymbolNumber]) -> SmolStr {
+        syms.iter()
+            .filter(|s| !self.is_tag(**s))
+            .map(|s| &*self.key_table[*s as usize])
+            .collect()
+    }
+
     #[inline(always)]
     pub fn key_table(&self) -> &Vec<SmolStr> {
         &self.key_table
@@ -48,6 +59,14 @@ impl TransducerAlphabet {
         self.operations.contains_key(&symbol)
     }
 
+    /// Multicharacter symbols starting with `+` are analysis tags in
+    /// Giellatekno-style lexicons.
+    #[inline(always)]
+    pub fn is_tag(&self, symbol: SymbolNumber) -> bool {
+        let key = &self.key_table[symbol as usize];
+        key.len() > 1 && key.starts_with('+')
+    }
+
     #[inline(always)]
     pub fn add_symbol(&mut self, string: &str) {
         self.string_to_symbol
@@ -80,6 +99,27 @@ impl TransducerAlphabet {
         self.length == 0
     }
 
+    /// The normalization form the symbols are written in, if any of them
+    /// tell: a precomposed letter means NFC, while a combining mark without
+    /// one means NFD.
+    pub fn normalization_form(&self) -> Option<NormalizationForm> {
+        let mut has_marks = false;
+
+        for key in self.key_table.iter().filter(|key| !key.is_ascii()) {
+            if NormalizationForm::Nfd.normalize(key) != key.as_str() {
+                return Some(NormalizationForm::Nfc);
+            }
+
+            has_marks |= key.chars().any(|c| GeneralCategory::of(c).is_mark());
+        }
+
+        if has_marks {
+            Some(NormalizationForm::Nfd)
+        } else {
+            None
+        }
+    }
+
     #[inline(always)]
     pub fn create_translator_from<F, T>(&mut self, mutator: &T) -> Vec<SymbolNumber>
     where
@@ -105,3 +145,147 @@ impl TransducerAlphabet {
         translator
     }
 }
+
+#[derive(Debug, Default)]
+struct TrieNode {
+    children: HashMap<char, u32>,
+    symbol: Option<SymbolNumber>,
+}
+
+/// Splits input text into the symbols of an alphabet by longest match, so
+/// that multicharacter symbols such as digraphs or letters with combining
+/// marks win over their individual characters.
+#[derive(Debug)]
+pub struct SymbolTrie {
+    nodes: Vec<TrieNode>,
+}
+
+impl SymbolTrie {
+    /// Builds a trie of every ordinary symbol in the alphabet. Flags, special
+    /// symbols and analysis tags are left out, as they never occur in input.
+    pub fn new(alphabet: &TransducerAlphabet) -> SymbolTrie {
+        let mut trie = SymbolTrie {
+            nodes: vec![TrieNode::default()],
+        };
+
+        for (key, &symbol) in alphabet.string_to_symbol().iter() {
+            if key.is_empty() || alphabet.is_tag(symbol) {
+                continue;
+            }
+
+            let mut node = 0;
+
+            for ch in key.chars() {
+                node = match trie.nodes[node].children.get(&ch) {
+                    Some(&next) => next as usize,
+                    None => {
+                        trie.nodes.push(TrieNode::default());
+                        let next = trie.nodes.len() - 1;
+                        trie.nodes[node].children.insert(ch, next as u32);
+                        next
+                    }
+                };
+            }
+
+            trie.nodes[node].symbol = Some(symbol);
+        }
+
+        trie
+    }
+
+    /// Segments `word` into symbols, taking the longest symbol that matches at
+    /// each position. Characters not covered by any symbol become `unknown`.
+    pub fn segment(&self, word: &str, unknown: SymbolNumber) -> Vec<SymbolNumber> {
+        let chars = word.chars().collect::<Vec<_>>();
+        let mut out = Vec::with_capacity(chars.len());
+        let mut i = 0;
+
+        while i < chars.len() {
+            let mut node = 0;
+            let mut longest = None;
+
+            for (n, ch) in chars[i..].iter().enumerate() {
+                node = match self.nodes[node].children.get(ch) {
+                    Some(&next) => next as usize,
+                    None => break,
+                };
+
+                if let Some(symbol) = self.nodes[node].symbol {
+                    longest = Some((symbol, n + 1));
+                }
+            }
+
+            match longest {
+                Some((symbol, len)) => {
+                    out.push(symbol);
+                    i += len;
+                }
+                None => {
+                    out.push(unknown);
+                    i += 1;
+                }
+            }
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alphabet(keys: &[&str]) -> TransducerAlphabet {
+        let key_table = keys.iter().map(|k| SmolStr::from(*k)).collect::<Vec<_>>();
+        let string_to_symbol = key_table
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, k)| (k.clone(), i as SymbolNumber))
+            .collect();
+
+        TransducerAlphabet {
+            initial_symbol_count: key_table.len() as SymbolNumber,
+            key_table,
+            flag_state_size: 0,
+            length: 0,
+            string_to_symbol,
+            operations: HashMap::new(),
+            identity_symbol: None,
+            unknown_symbol: None,
+        }
+    }
+
+    #[test]
+    fn segment_longest_match() {
+        let alphabet = alphabet(&["", "d", "z", "dz", "dzs", "a", "a\u{301}", "+N"]);
+        let trie = SymbolTrie::new(&alphabet);
+
+        assert_eq!(trie.segment("dzsa", 0), vec![4, 5]);
+        assert_eq!(trie.segment("dza\u{301}", 0), vec![3, 6]);
+        assert_eq!(trie.segment("dzd", 0), vec![3, 1]);
+        assert_eq!(trie.segment("xa+N", 0), vec![0, 5, 0, 0]);
+    }
+
+    #[test]
+    fn normalization_form() {
+        let nfc = alphabet(&["", "a", "\u{e1}", "\u{301}", "+N"]);
+        let nfd = alphabet(&["", "a", "\u{301}", "@P.X.Y@"]);
+        let multichar = alphabet(&["", "a", "a\u{301}"]);
+        let plain = alphabet(&["", "a", "b", "+N"]);
+
+        assert_eq!(nfc.normalization_form(), Some(NormalizationForm::Nfc));
+        assert_eq!(nfd.normalization_form(), Some(NormalizationForm::Nfd));
+        assert_eq!(multichar.normalization_form(), Some(NormalizationForm::Nfd));
+        assert_eq!(plain.normalization_form(), None);
+        assert_eq!(
+            NormalizationForm::of("a\u{301}"),
+            Some(NormalizationForm::Nfd)
+        );
+        assert_eq!(
+            NormalizationForm::of("\u{e1}"),
+            Some(NormalizationForm::Nfc)
+        );
+        assert_eq!(NormalizationForm::Nfc.normalize("a\u{301}"), "\u{e1}");
+    }
+}
@@ -0,0 +1,446 @@
+//! An error model generated from an alphabet instead of loaded from a file.
+//!
+//! `EditDistanceTransducer` behaves like a compiled Levenshtein (or, with
+//! transpositions, Damerau-Levenshtein) error model over the symbols of a
+//! lexicon: every symbol may be kept for free, or substituted, deleted,
+//! inserted or swapped with the next one at a configurable cost, up to a
+//! maximum number of edits. Its arcs are computed when asked for, so nothing
+//! is stored beyond the alphabet.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use smol_str::SmolStr;
+
+use super::symbol_transition::SymbolTransition;
+use super::{Transducer, TransducerAlphabet, TransducerError};
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+use crate::vfs::{self, Filesystem};
+
+/// The costs and limit of a generated edit distance error model.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditDistanceConfig {
+    pub max_distance: usize,
+    pub insertion: Weight,
+    pub deletion: Weight,
+    pub substitution: Weight,
+    /// The cost of swapping two adjacent symbols, or `None` for plain
+    /// Levenshtein distance.
+    pub transposition: Option<Weight>,
+}
+
+impl Default for EditDistanceConfig {
+    fn default() -> EditDistanceConfig {
+        EditDistanceConfig {
+            max_distance: 2,
+            insertion: 1.0,
+            deletion: 1.0,
+            substitution: 1.0,
+            transposition: Some(1.0),
+        }
+    }
+}
+
+/// An edit distance error model over the ordinary symbols of a lexicon's
+/// alphabet.
+///
+/// Its states are the edit states `0..=max_distance`, counting the edits
+/// made so far, followed by one state per possible half-done transposition.
+/// Input symbols missing from the alphabet are read as its unknown symbol,
+/// which can only be substituted or deleted.
+#[derive(Debug)]
+pub struct EditDistanceTransducer {
+    alphabet: TransducerAlphabet,
+    config: EditDistanceConfig,
+    symbol_count: u32,
+    unknown: SymbolNumber,
+    slots: u32,
+}
+
+impl EditDistanceTransducer {
+    /// Generates an error model for a lexicon with the given alphabet. Flags,
+    /// special symbols and analysis tags are left out, as no user types them.
+    ///
+    /// Panics if the alphabet and maximum distance are too large for the
+    /// model's states to be numbered.
+    pub fn new(lexicon_alphabet: &TransducerAlphabet, config: EditDistanceConfig) -> Self {
+        let mut symbols = lexicon_alphabet
+            .string_to_symbol()
+            .iter()
+            .filter(|(key, symbol)| !key.is_empty() && !lexicon_alphabet.is_tag(**symbol))
+            .map(|(key, symbol)| (*symbol, key.clone()))
+            .collect::<Vec<_>>();
+        symbols.sort();
+
+        let mut key_table = vec![SmolStr::from("")];
+        key_table.extend(symbols.into_iter().map(|(_, key)| key));
+
+        let symbol_count = (key_table.len() - 1) as u32;
+        let unknown = key_table.len() as SymbolNumber;
+        key_table.push(SmolStr::from("@_UNKNOWN_SYMBOL_@"));
+
+        let string_to_symbol = key_table
+            .iter()
+            .enumerate()
+            .take(symbol_count as usize + 1)
+            .skip(1)
+            .map(|(i, key)| (key.clone(), i as SymbolNumber))
+            .collect();
+
+        let alphabet = TransducerAlphabet {
+            initial_symbol_count: key_table.len() as SymbolNumber,
+            length: 0,
+            key_table,
+            flag_state_size: 0,
+            string_to_symbol,
+            operations: Default::default(),
+            identity_symbol: None,
+            unknown_symbol: Some(unknown),
+        };
+
+        // Every state gets the same number of arc slots, one more than the
+        // most arcs any state has for a single input symbol.
+        let slots = 2 * symbol_count + 1;
+        let distance = config.max_distance as u64;
+        let state_count = distance + 1 + distance * u64::from(symbol_count).pow(2);
+        assert!(
+            state_count * u64::from(slots) <= u64::from(u32::MAX),
+            "alphabet too large for an edit distance of {}",
+            config.max_distance
+        );
+
+        EditDistanceTransducer {
+            alphabet,
+            config,
+            symbol_count,
+            unknown,
+            slots,
+        }
+    }
+
+    pub fn config(&self) -> &EditDistanceConfig {
+        &self.config
+    }
+
+    #[inline(always)]
+    fn edit_state_count(&self) -> u32 {
+        self.config.max_distance as u32 + 1
+    }
+
+    /// The state after reading `input` and writing `output` as the first half
+    /// of a transposition, with `edits` edits made before it.
+    #[inline(always)]
+    fn transposition_state(
+        &self,
+        edits: u32,
+        input: SymbolNumber,
+        output: SymbolNumber,
+    ) -> TransitionTableIndex {
+        let n = self.symbol_count;
+        self.edit_state_count() + (edits * n + u32::from(input) - 1) * n + u32::from(output) - 1
+    }
+
+    /// The symbol `index` places into the ordinary symbols other than `skip`.
+    #[inline(always)]
+    fn other_symbol(&self, skip: SymbolNumber, index: u32) -> SymbolNumber {
+        let symbol = index as SymbolNumber + 1;
+
+        if symbol >= skip {
+            symbol + 1
+        } else {
+            symbol
+        }
+    }
+
+    /// The arc in slot `slot` of `state` for `input`, where an `input` of 0
+    /// asks for the epsilon arcs.
+    fn arc(
+        &self,
+        state: TransitionTableIndex,
+        input: SymbolNumber,
+        slot: u32,
+    ) -> Option<SymbolTransition> {
+        let n = self.symbol_count;
+
+        if state >= self.edit_state_count() {
+            // The second half of a transposition writes the first symbol read.
+            let offset = state - self.edit_state_count();
+            let edits = offset / (n * n);
+            let first = (offset / n % n + 1) as SymbolNumber;
+            let second = (offset % n + 1) as SymbolNumber;
+
+            return if input == second && slot == 0 {
+                Some(transition(edits + 1, first, 0.0))
+            } else {
+                None
+            };
+        }
+
+        let edits = state;
+        let can_edit = edits < self.config.max_distance as u32;
+
+        if input == 0 {
+            return if can_edit && slot < n {
+                Some(transition(
+                    edits + 1,
+                    slot as SymbolNumber + 1,
+                    self.config.insertion,
+                ))
+            } else {
+                None
+            };
+        }
+
+        if input == self.unknown {
+            return if !can_edit {
+                None
+            } else if slot < n {
+                Some(transition(
+                    edits + 1,
+                    slot as SymbolNumber + 1,
+                    self.config.substitution,
+                ))
+            } else if slot == n {
+                Some(transition(edits + 1, 0, self.config.deletion))
+            } else {
+                None
+            };
+        }
+
+        if u32::from(input) > n {
+            return None;
+        }
+
+        // Identity, then n - 1 substitutions, a deletion and n - 1
+        // transpositions.
+        let others = n - 1;
+
+        if slot == 0 {
+            Some(transition(edits, input, 0.0))
+        } else if !can_edit {
+            None
+        } else if slot <= others {
+            Some(transition(
+                edits + 1,
+                self.other_symbol(input, slot - 1),
+                self.config.substitution,
+            ))
+        } else if slot == others + 1 {
+            Some(transition(edits + 1, 0, self.config.deletion))
+        } else if slot <= 2 * others + 1 {
+            let weight = self.config.transposition?;
+            let output = self.other_symbol(input, slot - others - 2);
+            Some(SymbolTransition::new(
+                Some(self.transposition_state(edits, input, output)),
+                Some(output),
+                Some(weight),
+            ))
+        } else {
+            None
+        }
+    }
+
+    #[inline(always)]
+    fn take(&self, i: TransitionTableIndex, input: SymbolNumber) -> Option<SymbolTransition> {
+        self.arc(i / self.slots, input, i % self.slots)
+    }
+}
+
+#[inline(always)]
+fn transition(
+    target: TransitionTableIndex,
+    output: SymbolNumber,
+    weight: Weight,
+) -> SymbolTransition {
+    SymbolTransition::new(Some(target), Some(output), Some(weight))
+}
+
+/// States are numbered as described on the type. As with the compiled
+/// transducers, `has_transitions` and `has_epsilons_or_flags` take a state
+/// plus one, and `next` returns the first of a state's arc slots, which are
+/// then walked one at a time by the `take_*` methods.
+impl<F: vfs::File> Transducer<F> for EditDistanceTransducer {
+    const FILE_EXT: &'static str = "";
+
+    /// Generated transducers have no file to load, so this always fails.
+    fn from_path<P, FS>(_fs: &FS, _path: P) -> Result<Self, TransducerError>
+    where
+        P: AsRef<Path>,
+        FS: Filesystem<File = F>,
+    {
+        Err(TransducerError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "edit distance transducers are generated, not loaded",
+        )))
+    }
+
+    #[inline(always)]
+    fn alphabet(&self) -> &TransducerAlphabet {
+        &self.alphabet
+    }
+
+    #[inline(always)]
+    fn mut_alphabet(&mut self) -> &mut TransducerAlphabet {
+        &mut self.alphabet
+    }
+
+    #[inline(always)]
+    fn transition_input_symbol(&self, i: TransitionTableIndex) -> Option<SymbolNumber> {
+        // Only epsilon arcs are ever asked about.
+        self.take(i, 0).map(|_| 0)
+    }
+
+    #[inline(always)]
+    fn has_transitions(&self, i: TransitionTableIndex, s: Option<SymbolNumber>) -> bool {
+        match s {
+            Some(symbol) if symbol != 0 => self.arc(i - 1, symbol, 0).is_some(),
+            _ => false,
+        }
+    }
+
+    #[inline(always)]
+    fn next(&self, i: TransitionTableIndex, _symbol: SymbolNumber) -> Option<TransitionTableIndex> {
+        Some(i * self.slots)
+    }
+
+    #[inline(always)]
+    fn has_epsilons_or_flags(&self, i: TransitionTableIndex) -> bool {
+        self.arc(i - 1, 0, 0).is_some()
+    }
+
+    #[inline(always)]
+    fn take_epsilons_and_flags(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
+        self.take(i, 0)
+    }
+
+    #[inline(always)]
+    fn take_epsilons(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
+        self.take(i, 0)
+    }
+
+    #[inline(always)]
+    fn take_non_epsilons(
+        &self,
+        i: TransitionTableIndex,
+        symbol: SymbolNumber,
+    ) -> Option<SymbolTransition> {
+        if symbol == 0 {
+            return None;
+        }
+
+        self.take(i, symbol)
+    }
+
+    #[inline(always)]
+    fn is_final(&self, i: TransitionTableIndex) -> bool {
+        i < self.edit_state_count()
+    }
+
+    #[inline(always)]
+    fn final_weight(&self, i: TransitionTableIndex) -> Option<Weight> {
+        if i < self.edit_state_count() {
+            Some(0.0)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hashbrown::HashMap;
+
+    fn lexicon_alphabet(keys: &[&str]) -> TransducerAlphabet {
+        let key_table = keys.iter().map(|k| SmolStr::from(*k)).collect::<Vec<_>>();
+        let string_to_symbol = key_table
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, k)| (k.clone(), i as SymbolNumber))
+            .collect();
+
+        TransducerAlphabet {
+            initial_symbol_count: key_table.len() as SymbolNumber,
+            length: 0,
+            key_table,
+            flag_state_size: 0,
+            string_to_symbol,
+            operations: HashMap::new(),
+            identity_symbol: None,
+            unknown_symbol: None,
+        }
+    }
+
+    fn arcs(
+        t: &EditDistanceTransducer,
+        state: TransitionTableIndex,
+        input: SymbolNumber,
+    ) -> Vec<(TransitionTableIndex, SymbolNumber, Weight)> {
+        let mut i = Transducer::<std::fs::File>::next(t, state, input).unwrap();
+        let mut out = vec![];
+
+        loop {
+            let transition = if input == 0 {
+                Transducer::<std::fs::File>::take_epsilons(t, i)
+            } else {
+                Transducer::<std::fs::File>::take_non_epsilons(t, i, input)
+            };
+
+            match transition {
+                Some(x) => out.push((
+                    x.target().unwrap(),
+                    x.symbol().unwrap(),
+                    x.weight().unwrap(),
+                )),
+                None => return out,
+            }
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn generated_arcs() {
+        let t = EditDistanceTransducer::new(
+            &lexicon_alphabet(&["", "a", "b", "c", "+N"]),
+            EditDistanceConfig {
+                max_distance: 1,
+                insertion: 1.0,
+                deletion: 2.0,
+                substitution: 3.0,
+                transposition: Some(4.0),
+            },
+        );
+
+        assert_eq!(t.alphabet.key_table.len(), 5);
+        assert_eq!(t.unknown, 4);
+
+        // Keep, substitute, delete, or start swapping "b".
+        let swap_ba = t.transposition_state(0, 2, 1);
+        let swap_bc = t.transposition_state(0, 2, 3);
+        assert_eq!(
+            arcs(&t, 0, 2),
+            vec![
+                (0, 2, 0.0),
+                (1, 1, 3.0),
+                (1, 3, 3.0),
+                (1, 0, 2.0),
+                (swap_ba, 1, 4.0),
+                (swap_bc, 3, 4.0),
+            ]
+        );
+        assert_eq!(arcs(&t, swap_ba, 1), vec![(1, 2, 0.0)]);
+        assert!(arcs(&t, swap_ba, 2).is_empty());
+
+        assert_eq!(arcs(&t, 0, 0), vec![(1, 1, 1.0), (1, 2, 1.0), (1, 3, 1.0)]);
+        assert_eq!(
+            arcs(&t, 0, 4),
+            vec![(1, 1, 3.0), (1, 2, 3.0), (1, 3, 3.0), (1, 0, 2.0)]
+        );
+
+        // No edits left after the first.
+        assert_eq!(arcs(&t, 1, 2), vec![(1, 2, 0.0)]);
+        assert!(arcs(&t, 1, 0).is_empty());
+        assert!(arcs(&t, 1, 4).is_empty());
+    }
+}
@@ -0,0 +1,255 @@
+//! A transducer built from a list of arcs, for testing the speller without
+//! compiled transducer files.
+
+use std::path::Path;
+
+use hashbrown::HashMap;
+
+use super::hfst::alphabet::TransducerAlphabetParser;
+use super::symbol_transition::SymbolTransition;
+use super::{Transducer, TransducerAlphabet, TransducerError};
+use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
+use crate::vfs::{self, Filesystem};
+
+#[derive(Debug, Clone, Copy)]
+struct Arc {
+    input: SymbolNumber,
+    output: SymbolNumber,
+    target: TransitionTableIndex,
+    weight: Weight,
+}
+
+/// A transducer held in memory. Each state's arcs sit in a run of slots,
+/// epsilons and flags first and then by input symbol, and an empty slot ends
+/// the run, as the `take_*` methods walk slots until one does not match.
+#[derive(Debug)]
+pub(crate) struct MemoryTransducer {
+    alphabet: TransducerAlphabet,
+    slots: Vec<Option<Arc>>,
+    first_slot: Vec<TransitionTableIndex>,
+    finals: Vec<Option<Weight>>,
+}
+
+impl MemoryTransducer {
+    /// Builds a transducer over `symbols`, which follow the epsilon and may
+    /// include flag diacritics such as `@P.X.Y@` and the identity and unknown
+    /// symbols. Arcs are `(from, to, input, output, weight)`, with the empty
+    /// string for epsilon, and state 0 is the start.
+    pub fn new(
+        symbols: &[&str],
+        arcs: &[(u32, u32, &str, &str, Weight)],
+        finals: &[(u32, Weight)],
+    ) -> MemoryTransducer {
+        let mut keys = vec!["@_EPSILON_SYMBOL_@"];
+        keys.extend_from_slice(symbols);
+
+        // The parser reads the alphabet off the front of a transducer file,
+        // so something other than padding has to follow it.
+        let mut buf = keys.join("\0").into_bytes();
+        buf.extend_from_slice(b"\0\0\x01");
+        let alphabet = TransducerAlphabetParser::parse(&buf, keys.len() as SymbolNumber);
+
+        let symbol = |key: &str| -> SymbolNumber {
+            if key.is_empty() {
+                return 0;
+            }
+
+            keys.iter()
+                .position(|k| *k == key)
+                .unwrap_or_else(|| panic!("no symbol {:?}", key)) as SymbolNumber
+        };
+
+        let state_count = arcs
+            .iter()
+            .map(|arc| arc.0.max(arc.1) + 1)
+            .chain(finals.iter().map(|(state, _)| state + 1))
+            .max()
+            .unwrap_or(1);
+
+        let mut by_state: HashMap<u32, Vec<Arc>> = HashMap::new();
+        for &(from, to, input, output, weight) in arcs {
+            by_state.entry(from).or_default().push(Arc {
+                input: symbol(input),
+                output: symbol(output),
+                target: to,
+                weight,
+            });
+        }
+
+        let mut slots = vec![];
+        let mut first_slot = vec![];
+
+        for state in 0..state_count {
+            let mut arcs = by_state.remove(&state).unwrap_or_default();
+            arcs.sort_by_key(|arc| {
+                let is_epsilon = arc.input == 0 || alphabet.is_flag(arc.input);
+                (!is_epsilon, arc.input)
+            });
+
+            first_slot.push(slots.len() as TransitionTableIndex);
+            slots.extend(arcs.into_iter().map(Some));
+            slots.push(None);
+        }
+
+        let mut final_weights = vec![None; state_count as usize];
+        for &(state, weight) in finals {
+            final_weights[state as usize] = Some(weight);
+        }
+
+        MemoryTransducer {
+            alphabet,
+            slots,
+            first_slot,
+            finals: final_weights,
+        }
+    }
+
+    /// Builds an acceptor for the given words and their weights, as a trie
+    /// with one symbol per character.
+    pub fn acceptor(words: &[(&str, Weight)]) -> MemoryTransducer {
+        let mut symbols = words
+            .iter()
+            .flat_map(|(word, _)| word.chars())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        symbols.sort();
+        symbols.dedup();
+
+        let mut arcs = vec![];
+        let mut finals = vec![];
+        let mut children: HashMap<(u32, char), u32> = HashMap::new();
+        let mut state_count = 1;
+
+        for (word, weight) in words {
+            let mut state = 0;
+
+            for c in word.chars() {
+                state = match children.get(&(state, c)) {
+                    Some(&next) => next,
+                    None => {
+                        let next = state_count;
+                        state_count += 1;
+                        children.insert((state, c), next);
+                        arcs.push((state, next, c.to_string()));
+                        next
+                    }
+                };
+            }
+
+            finals.push((state, *weight));
+        }
+
+        let symbols = symbols.iter().map(|s| &**s).collect::<Vec<_>>();
+        let arcs = arcs
+            .iter()
+            .map(|(from, to, key)| (*from, *to, &**key, &**key, 0.0))
+            .collect::<Vec<_>>();
+
+        MemoryTransducer::new(&symbols, &arcs, &finals)
+    }
+
+    #[inline(always)]
+    fn arc(&self, i: TransitionTableIndex) -> Option<Arc> {
+        self.slots.get(i as usize).copied().flatten()
+    }
+
+    #[inline(always)]
+    fn arcs(&self, state: TransitionTableIndex) -> impl Iterator<Item = Arc> + '_ {
+        self.slots[self.first_slot[state as usize] as usize..]
+            .iter()
+            .map_while(|arc| *arc)
+    }
+
+    #[inline(always)]
+    fn transition(arc: Arc) -> SymbolTransition {
+        SymbolTransition::new(Some(arc.target), Some(arc.output), Some(arc.weight))
+    }
+}
+
+/// As with the compiled transducers, `has_transitions` and
+/// `has_epsilons_or_flags` take a state plus one, and `next` returns the
+/// first of a state's slots for a symbol.
+impl<F: vfs::File> Transducer<F> for MemoryTransducer {
+    const FILE_EXT: &'static str = "";
+
+    fn from_path<P, FS>(_fs: &FS, _path: P) -> Result<Self, TransducerError>
+    where
+        P: AsRef<Path>,
+        FS: Filesystem<File = F>,
+    {
+        Err(TransducerError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "memory transducers are built, not loaded",
+        )))
+    }
+
+    fn alphabet(&self) -> &TransducerAlphabet {
+        &self.alphabet
+    }
+
+    fn mut_alphabet(&mut self) -> &mut TransducerAlphabet {
+        &mut self.alphabet
+    }
+
+    fn transition_input_symbol(&self, i: TransitionTableIndex) -> Option<SymbolNumber> {
+        self.arc(i).map(|arc| arc.input)
+    }
+
+    fn has_transitions(&self, i: TransitionTableIndex, s: Option<SymbolNumber>) -> bool {
+        match s {
+            Some(symbol) if symbol != 0 => self.arcs(i - 1).any(|arc| arc.input == symbol),
+            _ => false,
+        }
+    }
+
+    fn next(&self, i: TransitionTableIndex, symbol: SymbolNumber) -> Option<TransitionTableIndex> {
+        let first = self.first_slot[i as usize];
+        let offset = self
+            .arcs(i)
+            .position(|arc| {
+                if symbol == 0 {
+                    arc.input == 0 || self.alphabet.is_flag(arc.input)
+                } else {
+                    arc.input == symbol
+                }
+            })
+            .unwrap_or_else(|| self.arcs(i).count());
+
+        Some(first + offset as TransitionTableIndex)
+    }
+
+    fn has_epsilons_or_flags(&self, i: TransitionTableIndex) -> bool {
+        self.arcs(i - 1)
+            .any(|arc| arc.input == 0 || self.alphabet.is_flag(arc.input))
+    }
+
+    fn take_epsilons_and_flags(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
+        self.arc(i)
+            .filter(|arc| arc.input == 0 || self.alphabet.is_flag(arc.input))
+            .map(Self::transition)
+    }
+
+    fn take_epsilons(&self, i: TransitionTableIndex) -> Option<SymbolTransition> {
+        self.arc(i)
+            .filter(|arc| arc.input == 0)
+            .map(Self::transition)
+    }
+
+    fn take_non_epsilons(
+        &self,
+        i: TransitionTableIndex,
+        symbol: SymbolNumber,
+    ) -> Option<SymbolTransition> {
+        self.arc(i)
+            .filter(|arc| symbol != 0 && arc.input == symbol)
+            .map(Self::transition)
+    }
+
+    fn is_final(&self, i: TransitionTableIndex) -> bool {
+        self.finals[i as usize].is_some()
+    }
+
+    fn final_weight(&self, i: TransitionTableIndex) -> Option<Weight> {
+        self.finals[i as usize]
+    }
+}
@@ -1,11 +1,14 @@
+pub mod edit_distance;
 pub mod hfst;
 pub mod thfst;
 
 mod alphabet;
+#[cfg(test)]
+pub(crate) mod memory;
 mod symbol_transition;
 pub(crate) mod tree_node;
 
-pub(crate) use self::alphabet::TransducerAlphabet;
+pub(crate) use self::alphabet::{SymbolTrie, TransducerAlphabet};
 
 use self::symbol_transition::SymbolTransition;
 use crate::types::{SymbolNumber, TransitionTableIndex, Weight};
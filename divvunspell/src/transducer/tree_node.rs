@@ -1,4 +1,4 @@
-use lifeguard::{Pool, Recycled};
+use hashbrown::HashMap;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
@@ -8,23 +8,222 @@ use crate::types::{
     TransitionTableIndex, Weight,
 };
 
-#[derive(Debug, Clone)]
+/// An error model arc taken on the path to a node, in the mutator's alphabet.
+/// Epsilon (symbol 0) on either side marks an insertion or a deletion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EditArc {
+    pub input: SymbolNumber,
+    pub output: SymbolNumber,
+    pub weight: Weight,
+}
+
+/// Per-search storage for the parts of a path that grow as it is extended.
+///
+/// Output strings and edit traces are stored as parent links, so extending a
+/// node is a single push instead of a copy of everything before it; the full
+/// string is only rebuilt for nodes that end up as results. Strings and flag
+/// states are interned, so equal ones share an id.
+///
+/// An arena can be `reset` and reused for another search, keeping its
+/// allocations. The default arena is empty and must be reset before use.
+#[derive(Debug, Default)]
+pub struct NodeArena {
+    strings: Vec<(u32, SymbolNumber)>,
+    string_ids: HashMap<(u32, SymbolNumber), u32>,
+    edits: Vec<(u32, EditArc)>,
+    flag_states: Vec<FlagDiacriticState>,
+    flag_state_ids: HashMap<FlagDiacriticState, u32>,
+    mutator_start_state: u32,
+}
+
+impl NodeArena {
+    pub fn new(
+        capacity: usize,
+        flag_state_size: usize,
+        mutator_flag_state_size: usize,
+    ) -> NodeArena {
+        let mut arena = NodeArena {
+            strings: Vec::with_capacity(capacity.max(1)),
+            string_ids: HashMap::with_capacity(capacity),
+            ..NodeArena::default()
+        };
+
+        arena.reset(flag_state_size, mutator_flag_state_size);
+        arena
+    }
+
+    /// Empties the arena for a new search, keeping its allocations.
+    pub fn reset(&mut self, flag_state_size: usize, mutator_flag_state_size: usize) {
+        self.strings.clear();
+        self.strings.push((0, 0));
+        self.string_ids.clear();
+
+        self.edits.clear();
+        self.edits.push((
+            0,
+            EditArc {
+                input: 0,
+                output: 0,
+                weight: 0.0,
+            },
+        ));
+
+        self.flag_states.clear();
+        self.flag_state_ids.clear();
+        self.intern_flag_state(vec![0; flag_state_size]);
+        self.mutator_start_state = self.intern_flag_state(vec![0; mutator_flag_state_size]);
+    }
+
+    /// Room for strings and edits before the arena has to grow.
+    pub fn capacity(&self) -> usize {
+        self.strings.capacity().max(self.edits.capacity())
+    }
+
+    /// Frees the room held beyond `capacity` strings, edits and flag states.
+    /// The arena must be reset before it is used again.
+    pub fn shrink_to(&mut self, capacity: usize) {
+        self.strings.clear();
+        self.strings.shrink_to(capacity);
+        self.string_ids.clear();
+        self.string_ids.shrink_to(capacity);
+        self.edits.clear();
+        self.edits.shrink_to(capacity);
+        self.flag_states.clear();
+        self.flag_states.shrink_to(capacity);
+        self.flag_state_ids.clear();
+        self.flag_state_ids.shrink_to(capacity);
+    }
+
+    #[inline(always)]
+    fn push_symbol(&mut self, string: u32, symbol: SymbolNumber) -> u32 {
+        let strings = &mut self.strings;
+
+        *self.string_ids.entry((string, symbol)).or_insert_with(|| {
+            strings.push((string, symbol));
+            (strings.len() - 1) as u32
+        })
+    }
+
+    #[inline(always)]
+    pub fn push_edit(&mut self, edits: u32, edit: EditArc) -> u32 {
+        self.edits.push((edits, edit));
+        (self.edits.len() - 1) as u32
+    }
+
+    #[inline(always)]
+    fn flag_state(&self, id: u32) -> &FlagDiacriticState {
+        &self.flag_states[id as usize]
+    }
+
+    fn with_flag(&mut self, id: u32, feature: SymbolNumber, value: i16) -> u32 {
+        if self.flag_states[id as usize][feature as usize] == value {
+            return id;
+        }
+
+        let mut state = self.flag_states[id as usize].clone();
+        state[feature as usize] = value;
+        self.intern_flag_state(state)
+    }
+
+    fn intern_flag_state(&mut self, state: FlagDiacriticState) -> u32 {
+        if let Some(id) = self.flag_state_ids.get(&state) {
+            return *id;
+        }
+
+        self.flag_states.push(state.clone());
+        let id = (self.flag_states.len() - 1) as u32;
+        self.flag_state_ids.insert(state, id);
+        id
+    }
+
+    /// Rebuilds the output string with the given id.
+    pub fn string(&self, mut id: u32) -> Vec<SymbolNumber> {
+        let mut out = vec![];
+
+        while id != 0 {
+            let (parent, symbol) = self.strings[id as usize];
+            out.push(symbol);
+            id = parent;
+        }
+
+        out.reverse();
+        out
+    }
+
+    /// Rebuilds the edit trace with the given id.
+    pub fn edits(&self, mut id: u32) -> Vec<EditArc> {
+        let mut out = vec![];
+
+        while id != 0 {
+            let (parent, edit) = self.edits[id as usize];
+            out.push(edit);
+            id = parent;
+        }
+
+        out.reverse();
+        out
+    }
+
+    /// Whether the output string with the given id contains any of `symbols`.
+    pub fn has_any_symbol(&self, mut id: u32, symbols: &[SymbolNumber]) -> bool {
+        if symbols.is_empty() {
+            return false;
+        }
+
+        while id != 0 {
+            let (parent, symbol) = self.strings[id as usize];
+            if symbols.contains(&symbol) {
+                return true;
+            }
+            id = parent;
+        }
+
+        false
+    }
+}
+
+/// A point in the search. The output string, edit trace and flag state are
+/// ids into the search's `NodeArena`.
+#[derive(Debug, Clone, Copy)]
 pub struct TreeNode {
+    /// Which of the speller's lexicons `lexicon_state` belongs to.
+    pub lexicon_index: u32,
     pub lexicon_state: TransitionTableIndex,
     pub mutator_state: TransitionTableIndex,
     pub input_state: u32,
     pub weight: f32,
-    pub flag_state: FlagDiacriticState,
-    pub string: Vec<SymbolNumber>,
+    pub flag_state: u32,
+    pub mutator_flag_state: u32,
+    pub string: u32,
+    pub edits: u32,
+}
+
+/// The part of a `TreeNode` that decides where the search can go from it.
+///
+/// Two nodes with equal keys lead to exactly the same continuations, so only
+/// the lighter one is worth expanding. The output string is part of the key
+/// because a minimized lexicon reaches the same state from different
+/// prefixes, and those still make for different suggestions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TreeNodeKey {
+    input_state: u32,
+    mutator_state: TransitionTableIndex,
+    lexicon_index: u32,
+    lexicon_state: TransitionTableIndex,
+    flag_state: u32,
+    mutator_flag_state: u32,
+    string: u32,
 }
 
 impl std::cmp::PartialEq for TreeNode {
     fn eq(&self, other: &TreeNode) -> bool {
-        self.lexicon_state == other.lexicon_state
+        self.lexicon_index == other.lexicon_index
+            && self.lexicon_state == other.lexicon_state
             && self.mutator_state == other.mutator_state
             && self.input_state == other.input_state
             && self.weight == other.weight
             && self.flag_state == other.flag_state
+            && self.mutator_flag_state == other.mutator_flag_state
             && self.string == other.string
     }
 }
@@ -58,59 +257,22 @@ impl Hash for TreeNode {
     }
 }
 
-impl lifeguard::Recycleable for TreeNode {
-    fn new() -> Self {
-        TreeNode {
-            string: Vec::with_capacity(1),
-            input_state: 0,
-            mutator_state: 0,
-            lexicon_state: 0,
-            flag_state: vec![],
-            weight: 0.0,
-        }
-    }
-
-    fn reset(&mut self) {
-        // There is nothing done to reset it.
-        // Implementers must reset any fields where used!
-    }
-}
-
-impl lifeguard::InitializeWith<&TreeNode> for TreeNode {
-    fn initialize_with(&mut self, source: &TreeNode) {
-        if self.string != source.string {
-            self.string.truncate(0);
-            self.string.extend(&source.string);
-        }
-
-        self.input_state = source.input_state;
-        self.mutator_state = source.mutator_state;
-        self.lexicon_state = source.lexicon_state;
-
-        if self.flag_state != source.flag_state {
-            self.flag_state.truncate(0);
-            self.flag_state
-                .extend_from_slice(&source.flag_state.as_slice());
-        }
-
-        self.weight = source.weight;
-    }
-}
-
 impl TreeNode {
+    /// The start node in the first lexicon, with an empty string and the
+    /// arena's start flag states.
     #[inline(always)]
-    pub fn empty<'a>(
-        pool: &'a Pool<TreeNode>,
-        start_state: FlagDiacriticState,
-    ) -> Recycled<'a, TreeNode> {
-        pool.attach(TreeNode {
-            string: vec![],
+    pub fn empty(arena: &NodeArena) -> TreeNode {
+        TreeNode {
+            string: 0,
             input_state: 0,
             mutator_state: 0,
+            lexicon_index: 0,
             lexicon_state: 0,
-            flag_state: start_state,
+            flag_state: 0,
+            mutator_flag_state: arena.mutator_start_state,
             weight: 0.0,
-        })
+            edits: 0,
+        }
     }
 
     #[inline(always)]
@@ -119,196 +281,161 @@ impl TreeNode {
     }
 
     #[inline(always)]
-    pub fn update_lexicon<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        transition: SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
+    pub fn key(&self) -> TreeNodeKey {
+        TreeNodeKey {
+            input_state: self.input_state,
+            mutator_state: self.mutator_state,
+            lexicon_index: self.lexicon_index,
+            lexicon_state: self.lexicon_state,
+            flag_state: self.flag_state,
+            mutator_flag_state: self.mutator_flag_state,
+            string: self.string,
         }
+    }
+
+    #[inline(always)]
+    pub fn update_lexicon(&self, arena: &mut NodeArena, transition: SymbolTransition) -> TreeNode {
+        let mut node = *self;
 
         if let Some(value) = transition.symbol() {
             if value != 0 {
-                node.string.push(value);
+                node.string = arena.push_symbol(self.string, value);
             }
         }
 
-        node.input_state = self.input_state;
-        node.mutator_state = self.mutator_state;
         node.lexicon_state = transition.target().unwrap();
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state
-                .extend_from_slice(&self.flag_state.as_slice());
-        }
-
         node.weight = self.weight + transition.weight().unwrap();
-
         node
     }
 
     #[inline(always)]
-    pub fn update_mutator<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        transition: SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
-        node.input_state = self.input_state;
+    pub fn update_mutator(&self, transition: SymbolTransition) -> TreeNode {
+        let mut node = *self;
         node.mutator_state = transition.target().unwrap();
-        node.lexicon_state = self.lexicon_state;
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state
-                .extend_from_slice(&self.flag_state.as_slice());
-        }
-
         node.weight = self.weight + transition.weight().unwrap();
         node
     }
 
     #[inline(always)]
-    pub fn update<'a>(
+    pub fn update(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         output_symbol: SymbolNumber,
         next_input: Option<u32>,
         next_mutator: TransitionTableIndex,
         next_lexicon: TransitionTableIndex,
         weight: Weight,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
+    ) -> TreeNode {
+        let mut node = *self;
 
         if output_symbol != 0 {
-            node.string.push(output_symbol);
+            node.string = arena.push_symbol(self.string, output_symbol);
         }
 
         node.mutator_state = next_mutator;
         node.lexicon_state = next_lexicon;
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state
-                .extend_from_slice(&self.flag_state.as_slice());
-        }
-
         node.weight = self.weight + weight;
 
         if let Some(input) = next_input {
             node.input_state = input;
-        } else {
-            node.input_state = self.input_state;
         }
 
         node
     }
 
     #[inline(always)]
-    fn update_flag<'a>(
-        &self,
-        pool: &'a Pool<TreeNode>,
-        feature: SymbolNumber,
-        value: i16,
-        transition: &SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = self.apply_transition(pool, transition);
-        node.flag_state[feature as usize] = value;
+    pub fn apply_transition(&self, transition: &SymbolTransition) -> TreeNode {
+        let mut node = *self;
+        node.lexicon_state = transition.target().unwrap();
+        node.weight = self.weight + transition.weight().unwrap();
         node
     }
 
+    /// Follows a lexicon flag diacritic arc, if the flag state allows it.
     #[inline(always)]
-    pub fn apply_transition<'a>(
+    pub fn apply_operation(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
+        op: &FlagDiacriticOperation,
         transition: &SymbolTransition,
-    ) -> Recycled<'a, TreeNode> {
-        let mut node = pool.new();
-
-        if node.string != self.string {
-            node.string.truncate(0);
-            node.string.extend(&self.string);
-        }
+    ) -> Option<TreeNode> {
+        let value = evaluate_operation(op, arena.flag_state(self.flag_state))?;
+        let mut node = self.apply_transition(transition);
 
-        node.input_state = self.input_state;
-        node.mutator_state = self.mutator_state;
-        node.lexicon_state = transition.target().unwrap();
-
-        if node.flag_state != self.flag_state {
-            node.flag_state.truncate(0);
-            node.flag_state
-                .extend_from_slice(&self.flag_state.as_slice());
+        if let Some(value) = value {
+            node.flag_state = arena.with_flag(self.flag_state, op.feature, value);
         }
 
-        node.weight = self.weight + transition.weight().unwrap();
-        node
+        Some(node)
     }
 
+    /// Follows an error model flag diacritic arc, if the error model's own
+    /// flag state allows it.
     #[inline(always)]
-    pub fn apply_operation<'a>(
+    pub fn apply_mutator_operation(
         &self,
-        pool: &'a Pool<TreeNode>,
+        arena: &mut NodeArena,
         op: &FlagDiacriticOperation,
         transition: &SymbolTransition,
-    ) -> Option<Recycled<'a, TreeNode>> {
-        match op.operation {
-            FlagDiacriticOperator::PositiveSet => {
-                Some(self.update_flag(pool, op.feature, op.value, transition))
-            }
-            FlagDiacriticOperator::NegativeSet => {
-                Some(self.update_flag(pool, op.feature, -op.value, transition))
-            }
-            FlagDiacriticOperator::Require => {
-                let res = if op.value == 0 {
-                    self.flag_state[op.feature as usize] != 0
-                } else {
-                    self.flag_state[op.feature as usize] == op.value
-                };
-
-                if res {
-                    Some(self.apply_transition(pool, transition))
-                } else {
-                    None
-                }
+    ) -> Option<TreeNode> {
+        let value = evaluate_operation(op, arena.flag_state(self.mutator_flag_state))?;
+        let mut node = self.update_mutator(transition.clone());
+
+        if let Some(value) = value {
+            node.mutator_flag_state = arena.with_flag(self.mutator_flag_state, op.feature, value);
+        }
+
+        Some(node)
+    }
+}
+
+/// Checks a flag diacritic operation against a flag state. Returns `None` if
+/// the operation fails, otherwise the new value of the operation's feature if
+/// it changes one.
+#[inline(always)]
+fn evaluate_operation(
+    op: &FlagDiacriticOperation,
+    flag_state: &FlagDiacriticState,
+) -> Option<Option<i16>> {
+    let current = flag_state[op.feature as usize];
+
+    match op.operation {
+        FlagDiacriticOperator::PositiveSet => Some(Some(op.value)),
+        FlagDiacriticOperator::NegativeSet => Some(Some(-op.value)),
+        FlagDiacriticOperator::Require => {
+            let res = if op.value == 0 {
+                current != 0
+            } else {
+                current == op.value
+            };
+
+            if res {
+                Some(None)
+            } else {
+                None
             }
-            FlagDiacriticOperator::Disallow => {
-                let res = if op.value == 0 {
-                    self.flag_state[op.feature as usize] == 0
-                } else {
-                    self.flag_state[op.feature as usize] != op.value
-                };
-
-                if res {
-                    Some(self.apply_transition(pool, transition))
-                } else {
-                    None
-                }
+        }
+        FlagDiacriticOperator::Disallow => {
+            let res = if op.value == 0 {
+                current == 0
+            } else {
+                current != op.value
+            };
+
+            if res {
+                Some(None)
+            } else {
+                None
             }
-            FlagDiacriticOperator::Clear => Some(self.update_flag(pool, op.feature, 0, transition)),
-            FlagDiacriticOperator::Unification => {
-                // if the feature is unset OR the feature is to this value already OR
-                // the feature is negatively set to something else than this value
-                let f = self.flag_state[op.feature as usize];
-
-                if f == 0 || f == op.value || (f < 0 && -f != op.value) {
-                    Some(self.update_flag(pool, op.feature, op.value, transition))
-                } else {
-                    None
-                }
+        }
+        FlagDiacriticOperator::Clear => Some(Some(0)),
+        FlagDiacriticOperator::Unification => {
+            // if the feature is unset OR the feature is to this value already OR
+            // the feature is negatively set to something else than this value
+            if current == 0 || current == op.value || (current < 0 && -current != op.value) {
+                Some(Some(op.value))
+            } else {
+                None
             }
         }
     }
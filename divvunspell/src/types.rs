@@ -1,4 +1,7 @@
+use std::borrow::Cow;
+
 use serde::{Deserialize, Serialize};
+use unic_normal::StrNormalForm;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum FlagDiacriticOperator {
@@ -26,6 +29,63 @@ impl std::str::FromStr for FlagDiacriticOperator {
     }
 }
 
+/// A Unicode normalization form: precomposed letters such as "á" (NFC), or
+/// base letters followed by combining marks (NFD).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NormalizationForm {
+    Nfc,
+    Nfd,
+}
+
+impl NormalizationForm {
+    /// The form `text` is written in, or `None` if it reads the same in both,
+    /// such as plain ASCII, or mixes the two.
+    pub fn of(text: &str) -> Option<NormalizationForm> {
+        if text.is_ascii() {
+            return None;
+        }
+
+        let is_nfc = text.nfc().eq(text.chars());
+        let is_nfd = text.nfd().eq(text.chars());
+
+        match (is_nfc, is_nfd) {
+            (true, false) => Some(NormalizationForm::Nfc),
+            (false, true) => Some(NormalizationForm::Nfd),
+            _ => None,
+        }
+    }
+
+    /// `text` in this form, borrowed if it already is.
+    pub fn normalize(self, text: &str) -> Cow<'_, str> {
+        if text.is_ascii() {
+            return Cow::Borrowed(text);
+        }
+
+        let normalized: String = match self {
+            NormalizationForm::Nfc => text.nfc().collect(),
+            NormalizationForm::Nfd => text.nfd().collect(),
+        };
+
+        if normalized == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(normalized)
+        }
+    }
+}
+
+impl std::str::FromStr for NormalizationForm {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "nfc" => Ok(NormalizationForm::Nfc),
+            "nfd" => Ok(NormalizationForm::Nfd),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum HeaderFlag {
     Weighted,
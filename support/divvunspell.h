@@ -66,6 +66,7 @@ struct SpellerConfig {
     float beam;
     struct CaseHandlingConfig case_handling;
     rust_usize_t node_pool_size;
+    uint64_t time_limit_ms;
 };
 
 extern const void *_Nullable
@@ -87,6 +88,14 @@ divvun_thfst_chunked_box_speller_suggest_with_config(
     struct SpellerConfig *_Nonnull config,
     ERR_CALLBACK);
 
+extern const void *_Nullable
+divvun_thfst_chunked_box_speller_suggest_in_context(
+    const void *_Nonnull speller,
+    const char *_Nonnull first_half,
+    const char *_Nonnull second_half,
+    struct SpellerConfig *_Nonnull config,
+    ERR_CALLBACK);
+
 extern const void *_Nullable
 divvun_thfst_box_speller_archive_open(const rust_path_t *_Nonnull path, ERR_CALLBACK);
 
@@ -106,6 +115,14 @@ divvun_thfst_box_speller_suggest_with_config(
     struct SpellerConfig *_Nonnull config,
     ERR_CALLBACK);
 
+extern const void *_Nullable
+divvun_thfst_box_speller_suggest_in_context(
+    const void *_Nonnull speller,
+    const char *_Nonnull first_half,
+    const char *_Nonnull second_half,
+    struct SpellerConfig *_Nonnull config,
+    ERR_CALLBACK);
+
 extern const void *_Nullable
 divvun_hfst_zip_speller_archive_open(const rust_path_t *_Nonnull path, ERR_CALLBACK);
 
@@ -128,6 +145,14 @@ divvun_hfst_zip_speller_suggest_with_config(
     struct SpellerConfig *_Nonnull config,
     ERR_CALLBACK);
 
+extern const void *_Nullable
+divvun_hfst_zip_speller_suggest_in_context(
+    const void *_Nonnull speller,
+    const char *_Nonnull first_half,
+    const char *_Nonnull second_half,
+    struct SpellerConfig *_Nonnull config,
+    ERR_CALLBACK);
+
 extern rust_usize_t
 divvun_vec_suggestion_len(const rust_slice_t suggestions, ERR_CALLBACK);
 